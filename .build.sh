@@ -1,4 +1,7 @@
 #!/bin/sh
 
 export LIBTCOD_SRC_DIR="/home/joe/libtcod"
-cp $LIBTCOD_SRC_DIR/terminal.png $OUT_DIR/../../../
+# The font is only there, and only needed, when building with libtcod.
+if [ -f "$LIBTCOD_SRC_DIR/terminal.png" ]; then
+    cp $LIBTCOD_SRC_DIR/terminal.png $OUT_DIR/../../../
+fi
@@ -0,0 +1,93 @@
+extern crate test;
+extern crate dwemthys;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use test::Bencher;
+
+use dwemthys::actor::Actor;
+use dwemthys::maps::{ Map, ActorId };
+use dwemthys::movement::MoveInfo;
+use dwemthys::util::{ Bound, Point };
+
+fn world() -> Bound {
+    Bound::new(0, 0, 239, 119)
+}
+
+fn step(point: Point, size: Bound) -> Point {
+    Point::new((point.x + 1) % size.max.x, point.y)
+}
+
+fn spawn_points(count: uint, size: Bound) -> Vec<Point> {
+    range(0, count).map(|i| {
+        let i = i as i32;
+        Point::new((i * 7) % size.max.x, (i * 13) % size.max.y)
+    }).collect()
+}
+
+// The grid rebuild `Map::update` used to do on every keypress: a fresh
+// cell grid and a clone of every actor just to move it.
+fn legacy_grid(size: Bound) -> Vec<Vec<Vec<Box<Actor>>>> {
+    let mut contents: Vec<Vec<Vec<Box<Actor>>>> = vec![];
+    for _ in range(0, size.max.x) {
+        let mut x_vec: Vec<Vec<Box<Actor>>> = vec![];
+        for _ in range(0, size.max.y) {
+            x_vec.push(vec![]);
+        }
+        contents.push(x_vec);
+    }
+    contents
+}
+
+fn legacy_update(content: &mut Vec<Vec<Vec<Box<Actor>>>>, size: Bound) {
+    let mut new_content = legacy_grid(size);
+    for x_iter in content.iter_mut() {
+        for y_iter in x_iter.iter_mut() {
+            for actor in y_iter.iter_mut() {
+                actor.position = step(actor.position, size);
+                let point = actor.position;
+                new_content[point.x as uint][point.y as uint].push(actor.clone());
+            }
+        }
+    }
+    *content = new_content;
+}
+
+fn bench_legacy(b: &mut Bencher, count: uint) {
+    let size = world();
+    let move_info = Rc::new(RefCell::new(MoveInfo::new(size, Point::new(0, 0))));
+    let mut content = legacy_grid(size);
+    for point in spawn_points(count, size).into_iter() {
+        content[point.x as uint][point.y as uint].push(box Actor::dog(point.x, point.y, move_info.clone()));
+    }
+
+    b.iter(|| legacy_update(&mut content, size));
+}
+
+fn bench_indexed(b: &mut Bencher, count: uint) {
+    let size = world();
+    let move_info = Rc::new(RefCell::new(MoveInfo::new(size, Point::new(0, 0))));
+    let mut map = Map::new(move_info.clone());
+    let ids: Vec<ActorId> = spawn_points(count, size).into_iter().map(|point| {
+        map.push_actor(point, box Actor::dog(point.x, point.y, move_info.clone()))
+    }).collect();
+
+    b.iter(|| {
+        for &id in ids.iter() {
+            let next = step(map.get(id).unwrap().position, size);
+            map.move_actor(id, next);
+        }
+    });
+}
+
+#[bench]
+fn legacy_rebuild_1k_actors(b: &mut Bencher) { bench_legacy(b, 1000) }
+
+#[bench]
+fn legacy_rebuild_10k_actors(b: &mut Bencher) { bench_legacy(b, 10000) }
+
+#[bench]
+fn indexed_update_1k_actors(b: &mut Bencher) { bench_indexed(b, 1000) }
+
+#[bench]
+fn indexed_update_10k_actors(b: &mut Bencher) { bench_indexed(b, 10000) }
@@ -0,0 +1,48 @@
+use util::Point;
+use input::{ Key, KeyCode };
+use input::Key::{ SpecialKey, Printable };
+use item::ItemKind;
+
+// Something the heroine has decided to do. Only `Maps::perform` carries them out,
+// and only the ones that actually happen cost her time.
+pub enum Action {
+    Move(Point),
+    Wait,
+    PickUp,
+    Drop(uint),
+    // Equips the pack item at the index, or takes it off if it is already equipped.
+    Equip(uint),
+    Use(ItemKind, Point),
+    Throw(ItemKind, Point),
+    Descend,
+    Ascend,
+    // Walks to the stairs down over as many turns as it takes.
+    Travel,
+    Quit
+}
+
+impl Action {
+    // Actions the map screen understands on their own; item actions are put together
+    // by the states that ask which item and direction to use.
+    pub fn from_key(key: Key) -> Option<Action> {
+        match key {
+            SpecialKey(KeyCode::Escape) => Some(Action::Quit),
+            Printable('.') => Some(Action::Wait),
+            Printable('g') => Some(Action::PickUp),
+            Printable('>') => Some(Action::Descend),
+            Printable('<') => Some(Action::Ascend),
+            Printable('t') => Some(Action::Travel),
+            _ => direction_from_key(key).map(|direction| Action::Move(direction))
+        }
+    }
+}
+
+pub fn direction_from_key(key: Key) -> Option<Point> {
+    match key {
+        SpecialKey(KeyCode::Up) => Some(Point::new(0, -1)),
+        SpecialKey(KeyCode::Down) => Some(Point::new(0, 1)),
+        SpecialKey(KeyCode::Left) => Some(Point::new(-1, 0)),
+        SpecialKey(KeyCode::Right) => Some(Point::new(1, 0)),
+        _ => None
+    }
+}
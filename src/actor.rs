@@ -4,49 +4,109 @@ use std::rc::Rc;
 use util::Point;
 use game::Windows;
 use rendering::RenderingComponent;
+use maps::Maps;
+use stats::Stats;
+use combat::Weapon;
+use item::ItemKind;
+use inventory::{ Inventory, DEFAULT_CAPACITY };
+use scheduler::ACTION_COST;
+use awareness::Awareness;
 use movement::{
     MoveInfo,
     MovementComponent,
     UserMovementComponent,
     RandomMovementComponent,
-    AgroMovementComponent
+    AgroMovementComponent,
+    HunterMovementComponent,
+    SkittishMovementComponent
 };
 
 pub struct Actor {
     pub position: Point,
     pub display_char: char,
+    pub name: String,
+    pub stats: Stats,
+    pub inventory: Inventory,
+    // Built up at the actor's speed and spent on actions, see the scheduler.
+    pub energy: i32,
+    // All the energy built up since arriving on the level, and the order of arrival;
+    // the scheduler settles ties with them.
+    pub earned: i32,
+    pub arrival: uint,
     pub is_pc: bool,
     movement_component: Box<MovementComponent + 'static>,
 }
 
 impl Actor {
-    pub fn new(x: i32, y: i32, dc: char, movement_component: Box<MovementComponent + 'static>, is_pc: bool) -> Actor {
-        Actor { position: Point { x: x, y: y }, display_char: dc, movement_component: movement_component , is_pc: is_pc }
+    pub fn new(x: i32, y: i32, dc: char, name: &str, stats: Stats, movement_component: Box<MovementComponent + 'static>, is_pc: bool) -> Actor {
+        Actor {
+            position: Point { x: x, y: y },
+            display_char: dc,
+            name: name.to_string(),
+            stats: stats,
+            inventory: Inventory::new(DEFAULT_CAPACITY),
+            energy: 0,
+            earned: 0,
+            arrival: 0,
+            movement_component: movement_component,
+            is_pc: is_pc
+        }
     }
 
     pub fn dog(x: i32, y: i32, move_info: Rc<RefCell<MoveInfo>>) -> Actor {
         let mc: Box<RandomMovementComponent> = box MovementComponent::new(move_info.clone());
-        Actor::new(x, y, 'd', mc, false)
+        Actor::new(x, y, 'd', "dog", Stats::new(10, 3, 1, 120), mc, false)
     }
 
     pub fn cat(x: i32, y: i32, move_info: Rc<RefCell<MoveInfo>>) -> Actor {
-        let mc: Box<RandomMovementComponent> = box MovementComponent::new(move_info.clone());
-        Actor::new(x, y, 'c', mc, false)
+        let mc: Box<SkittishMovementComponent> = box MovementComponent::new(move_info.clone());
+        Actor::new(x, y, 'c', "cat", Stats::new(8, 2, 1, 140), mc, false)
     }
 
     pub fn kobold(x: i32, y: i32, move_info: Rc<RefCell<MoveInfo>>) -> Actor {
         let mc: Box<AgroMovementComponent> = box MovementComponent::new(move_info.clone());
-        Actor::new(x, y, 'k', mc, false)
+        Actor::new(x, y, 'k', "kobold", Stats::new(12, 4, 1, 100), mc, false)
+    }
+
+    pub fn jackal(x: i32, y: i32, move_info: Rc<RefCell<MoveInfo>>) -> Actor {
+        let mc: Box<HunterMovementComponent> = box MovementComponent::new(move_info.clone());
+        Actor::new(x, y, 'j', "jackal", Stats::new(6, 3, 0, 130), mc, false)
     }
 
     pub fn heroine(move_info: Rc<RefCell<MoveInfo>>) -> Actor {
         let point = { move_info.borrow().deref().char_location };
         let mc: Box<UserMovementComponent> = box MovementComponent::new(move_info.clone());
-        Actor::new(point.x, point.y, '@', mc, true)
+        let mut heroine = Actor::new(point.x, point.y, '@', "heroine", Stats::new(30, 5, 2, 100), mc, true);
+        heroine.inventory.add(ItemKind::HeroicSword).ok();
+        heroine.inventory.add(ItemKind::DeliciousLettuce).ok();
+        heroine.inventory.equip(0);
+        // She always gets the first move.
+        heroine.energy = ACTION_COST;
+        heroine
+    }
+
+    // Base stats with everything the actor has equipped added on.
+    pub fn effective_stats(&self) -> Stats {
+        self.stats.with_bonus(&self.inventory.bonus())
+    }
+
+    pub fn wielded_weapon(&self) -> Weapon {
+        match self.inventory.wielded() {
+            Some(kind) => kind.weapon(),
+            None => Weapon::natural()
+        }
+    }
+
+    pub fn movement_name(&self) -> &'static str {
+        self.movement_component.registry_name()
+    }
+
+    pub fn awareness(&self) -> Option<&Awareness> {
+        self.movement_component.awareness()
     }
 
-    pub fn update(&mut self, windows: &mut Windows) {
-        self.position = self.movement_component.update(self.position, windows);
+    pub fn next_position(&self, maps: &Maps, windows: &mut Windows) -> Point {
+        self.movement_component.update(self.position, maps, windows)
     }
 
     pub fn render(&self, rendering_component: &mut Box<RenderingComponent>) {
@@ -57,6 +117,11 @@ impl Actor {
 impl Clone for Actor {
     fn clone(&self) -> Actor {
         let mc = self.movement_component.box_clone();
-        Actor::new(self.position.x, self.position.y, self.display_char, mc, self.is_pc)
+        let mut actor = Actor::new(self.position.x, self.position.y, self.display_char, self.name.as_slice(), self.stats, mc, self.is_pc);
+        actor.inventory = self.inventory.clone();
+        actor.energy = self.energy;
+        actor.earned = self.earned;
+        actor.arrival = self.arrival;
+        actor
     }
 }
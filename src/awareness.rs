@@ -0,0 +1,277 @@
+use std::cell::Cell;
+use std::rand::Rng;
+
+use util::Point;
+use maps::Maps;
+use dijkstra;
+
+// How far away, walking around walls, a monster can hear the heroine.
+static HEARING_RADIUS: i32 = 5;
+// Sleepers only notice a noise some of the time, unless it's right next to them.
+static WAKE_CHANCE: uint = 4;
+// How long a monster searches for the heroine after losing her, getting to where she
+// was last noticed included.
+static SEARCH_TURNS: uint = 20;
+
+#[deriving(PartialEq, Show, Encodable, Decodable)]
+pub enum State {
+    Asleep,
+    Wandering,
+    Hunting,
+    Searching
+}
+
+// What a monster knows about the heroine. It only learns where she is by seeing or
+// hearing her, and remembers the last place it did for when it loses track of her.
+// Movement components update it from `&self`, hence the cells.
+pub struct Awareness {
+    state: Cell<State>,
+    last_known: Cell<Option<Point>>,
+    search_turns: Cell<uint>
+}
+
+// Awareness without the cells, for save files.
+#[deriving(Encodable, Decodable)]
+pub struct SavedAwareness {
+    pub state: State,
+    pub last_known: Option<Point>,
+    pub search_turns: uint
+}
+
+impl Awareness {
+    pub fn new(state: State) -> Awareness {
+        Awareness { state: Cell::new(state), last_known: Cell::new(None), search_turns: Cell::new(0) }
+    }
+
+    // Starts some monsters off asleep so the heroine can sneak past them.
+    pub fn sometimes_asleep<R: Rng>(rng: &mut R) -> Awareness {
+        let state = if rng.gen() { State::Asleep } else { State::Wandering };
+        Awareness::new(state)
+    }
+
+    pub fn state(&self) -> State {
+        self.state.get()
+    }
+
+    // Where the heroine was last seen or heard, while the monster is still after her.
+    pub fn target(&self) -> Option<Point> {
+        match self.state.get() {
+            State::Hunting | State::Searching => self.last_known.get(),
+            _ => None
+        }
+    }
+
+    // Takes in what the monster at `position` can perceive this turn and moves on to
+    // the next state.
+    pub fn update(&self, position: Point, maps: &Maps) {
+        let heroine = match maps.heroine() {
+            Some(heroine) => heroine.position,
+            None => {
+                self.state.set(State::Wandering);
+                return;
+            }
+        };
+        let sees = can_see(maps, position);
+        let hears = can_hear(maps, position);
+
+        match self.state.get() {
+            State::Asleep => {
+                let is_adjacent = (heroine.x - position.x).abs() <= 1 && (heroine.y - position.y).abs() <= 1;
+                if hears && (is_adjacent || maps.roll(|rng| rng.gen_range(0, WAKE_CHANCE)) == 0) {
+                    self.hunt(heroine);
+                }
+            },
+            State::Wandering => {
+                if sees || hears {
+                    self.hunt(heroine);
+                }
+            },
+            State::Hunting | State::Searching if sees || hears => self.hunt(heroine),
+            State::Hunting => {
+                self.state.set(State::Searching);
+                self.search_turns.set(SEARCH_TURNS);
+            },
+            State::Searching => {
+                let arrived = match self.last_known.get() {
+                    Some(point) => point.x == position.x && point.y == position.y,
+                    None => true
+                };
+                if arrived {
+                    // Nothing here; sniff around for what's left of the search.
+                    self.last_known.set(None);
+                }
+                // Counts down on the way too, so a monster that can't reach the spot, or
+                // finds someone else standing on it, still gives up in the end.
+                let turns = self.search_turns.get();
+                if turns == 0 {
+                    self.state.set(State::Wandering);
+                    self.last_known.set(None);
+                } else {
+                    self.search_turns.set(turns - 1);
+                }
+            }
+        }
+    }
+
+    pub fn save(&self) -> SavedAwareness {
+        SavedAwareness {
+            state: self.state.get(),
+            last_known: self.last_known.get(),
+            search_turns: self.search_turns.get()
+        }
+    }
+
+    pub fn restore(&self, saved: &SavedAwareness) {
+        self.state.set(saved.state);
+        self.last_known.set(saved.last_known);
+        self.search_turns.set(saved.search_turns);
+    }
+
+    fn hunt(&self, heroine: Point) {
+        self.state.set(State::Hunting);
+        self.last_known.set(Some(heroine));
+    }
+}
+
+impl Clone for Awareness {
+    fn clone(&self) -> Awareness {
+        Awareness {
+            state: Cell::new(self.state.get()),
+            last_known: Cell::new(self.last_known.get()),
+            search_turns: Cell::new(self.search_turns.get())
+        }
+    }
+}
+
+// Sight is symmetric, so the monster sees the heroine exactly when she could see it.
+fn can_see(maps: &Maps, position: Point) -> bool {
+    match maps.heroine_fov {
+        Some(ref fov) => fov.is_visible(position),
+        None => false
+    }
+}
+
+// Sound carries around corners but not through walls, so this goes by walking distance.
+fn can_hear(maps: &Maps, position: Point) -> bool {
+    match maps.flow {
+        Some(ref flow) => match flow.chase.distance(position) {
+            Some(distance) => distance <= HEARING_RADIUS * dijkstra::STEP,
+            None => false
+        },
+        None => false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::{ Awareness, State, SEARCH_TURNS };
+    use actor::Actor;
+    use maps::Maps;
+    use mapgen::Level;
+    use movement::MoveInfo;
+    use terrain::{ Terrain, Tile };
+    use util::{ Bound, Point };
+
+    // A long room split by a wall with a single gap at the bottom.
+    fn maps_with_heroine_at(heroine: Point) -> Maps<'static> {
+        let bounds = Bound::new(0, 0, 39, 9);
+        let mut terrain = Terrain::new(bounds);
+        for y in range(1i32, 8) {
+            terrain.set(Point::new(20, y), Tile::Wall);
+        }
+        let move_info = Rc::new(RefCell::new(MoveInfo::new(bounds, heroine)));
+        let level = Level { terrain: terrain, start: heroine, exit: Point::new(38, 8), spawns: vec![], items: vec![] };
+        let mut maps = Maps::new(move_info.clone(), level, None);
+        maps.pcs.push_actor(heroine, box Actor::heroine(move_info));
+        maps.refresh_flow_maps();
+        maps.refresh_heroine_fov();
+        maps
+    }
+
+    fn move_heroine(maps: &mut Maps, to: Point) {
+        let id = maps.heroine_id().unwrap();
+        maps.pcs.move_actor(id, to);
+        maps.refresh_flow_maps();
+        maps.refresh_heroine_fov();
+    }
+
+    #[test]
+    fn sleepers_ignore_what_they_cannot_hear() {
+        let maps = maps_with_heroine_at(Point::new(5, 4));
+        let awareness = Awareness::new(State::Asleep);
+        awareness.update(Point::new(12, 4), &maps);
+        assert_eq!(awareness.state(), State::Asleep);
+    }
+
+    #[test]
+    fn sleepers_wake_when_the_heroine_is_next_to_them() {
+        let maps = maps_with_heroine_at(Point::new(5, 4));
+        let awareness = Awareness::new(State::Asleep);
+        awareness.update(Point::new(6, 4), &maps);
+        assert_eq!(awareness.state(), State::Hunting);
+    }
+
+    #[test]
+    fn wanderers_hunt_what_they_see() {
+        let maps = maps_with_heroine_at(Point::new(5, 4));
+        let awareness = Awareness::new(State::Wandering);
+        awareness.update(Point::new(13, 4), &maps);
+        assert_eq!(awareness.state(), State::Hunting);
+        let target = awareness.target().unwrap();
+        assert_eq!((target.x, target.y), (5, 4));
+    }
+
+    #[test]
+    fn walls_hide_the_heroine() {
+        let maps = maps_with_heroine_at(Point::new(18, 2));
+        let awareness = Awareness::new(State::Wandering);
+        awareness.update(Point::new(22, 2), &maps);
+        assert_eq!(awareness.state(), State::Wandering);
+    }
+
+    #[test]
+    fn hunters_search_where_they_lost_her() {
+        let mut maps = maps_with_heroine_at(Point::new(15, 2));
+        let awareness = Awareness::new(State::Wandering);
+        let monster = Point::new(10, 2);
+        awareness.update(monster, &maps);
+        assert_eq!(awareness.state(), State::Hunting);
+
+        // She slips behind the wall, out of sight and too far around to be heard.
+        move_heroine(&mut maps, Point::new(25, 2));
+        awareness.update(monster, &maps);
+        assert_eq!(awareness.state(), State::Searching);
+        let target = awareness.target().unwrap();
+        assert_eq!((target.x, target.y), (15, 2));
+    }
+
+    #[test]
+    fn searchers_give_up_on_a_spot_they_cannot_reach() {
+        let mut maps = maps_with_heroine_at(Point::new(15, 2));
+        let awareness = Awareness::new(State::Wandering);
+        let monster = Point::new(10, 2);
+        awareness.update(monster, &maps);
+        move_heroine(&mut maps, Point::new(25, 2));
+        awareness.update(monster, &maps);
+        assert_eq!(awareness.state(), State::Searching);
+
+        // Wall in where she was last seen, so the monster can never get there.
+        for x in range(14i32, 17) {
+            for y in range(1i32, 4) {
+                maps.terrain.set(Point::new(x, y), Tile::Wall);
+            }
+        }
+        maps.refresh_flow_maps();
+
+        for _ in range(0, SEARCH_TURNS) {
+            awareness.update(monster, &maps);
+            assert_eq!(awareness.state(), State::Searching);
+        }
+        awareness.update(monster, &maps);
+        assert_eq!(awareness.state(), State::Wandering);
+        assert!(awareness.target().is_none());
+    }
+}
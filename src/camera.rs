@@ -0,0 +1,49 @@
+use std::cmp::{ max, min };
+
+use util::{ Bound, Point };
+use util::Contains::{ DoesContain, DoesNotContain };
+
+pub struct Camera {
+    pub viewport: Bound,
+    pub offset: Point,
+    world: Bound
+}
+
+impl Camera {
+    pub fn new(viewport: Bound, world: Bound) -> Camera {
+        Camera { viewport: viewport, offset: world.min, world: world }
+    }
+
+    pub fn follow(&mut self, target: Point) {
+        let width = self.viewport.width();
+        let height = self.viewport.height();
+
+        self.offset = Point::new(
+            Camera::clamp(target.x - width / 2, self.world.min.x, self.world.max.x - width + 1),
+            Camera::clamp(target.y - height / 2, self.world.min.y, self.world.max.y - height + 1)
+        );
+    }
+
+    pub fn to_screen(&self, point: Point) -> Option<Point> {
+        let screen = Point::new(
+            point.x - self.offset.x + self.viewport.min.x,
+            point.y - self.offset.y + self.viewport.min.y
+        );
+        match self.viewport.contains(screen) {
+            DoesContain => Some(screen),
+            DoesNotContain => None
+        }
+    }
+
+    pub fn to_world(&self, screen: Point) -> Point {
+        Point::new(
+            screen.x - self.viewport.min.x + self.offset.x,
+            screen.y - self.viewport.min.y + self.offset.y
+        )
+    }
+
+    fn clamp(value: i32, low: i32, high: i32) -> i32 {
+        // A world narrower than the viewport just stays pinned to its top left corner.
+        max(low, min(value, high))
+    }
+}
@@ -0,0 +1,13 @@
+// Colours are our own so that only the libtcod backend needs to know about libtcod.
+#[deriving(Clone, PartialEq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8
+}
+
+impl Color {
+    pub fn new(r: u8, g: u8, b: u8) -> Color {
+        Color { r: r, g: g, b: b }
+    }
+}
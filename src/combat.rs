@@ -0,0 +1,55 @@
+use std::cmp::{ max, min };
+use std::rand::Rng;
+
+use stats::Stats;
+
+pub struct Weapon {
+    pub name: String,
+    pub accuracy: i32,
+    pub min_damage: i32,
+    pub max_damage: i32
+}
+
+pub enum AttackResult {
+    Miss,
+    Hit(i32),
+    Kill(i32)
+}
+
+impl Weapon {
+    pub fn new(name: &str, accuracy: i32, min_damage: i32, max_damage: i32) -> Weapon {
+        Weapon { name: name.to_string(), accuracy: accuracy, min_damage: min_damage, max_damage: max_damage }
+    }
+
+    pub fn natural() -> Weapon {
+        Weapon::new("bare hands", 75, 1, 3)
+    }
+
+}
+
+pub fn hit_chance(attacker: &Stats, weapon: &Weapon, defender: &Stats) -> i32 {
+    // Every point of attack over the defender's defense is worth 5% either way.
+    let chance = weapon.accuracy + 5 * (attacker.attack - defender.defense);
+    max(5, min(95, chance))
+}
+
+pub fn resolve_attack<R: Rng>(rng: &mut R, attacker: &Stats, weapon: &Weapon, defender: &mut Stats) -> AttackResult {
+    if rng.gen_range(0i32, 100) >= hit_chance(attacker, weapon, defender) {
+        return AttackResult::Miss;
+    }
+
+    roll_damage(rng, attacker, weapon, defender)
+}
+
+// Damage for attacks that can't miss, like explosions.
+pub fn roll_damage<R: Rng>(rng: &mut R, attacker: &Stats, weapon: &Weapon, defender: &mut Stats) -> AttackResult {
+    let roll = rng.gen_range(weapon.min_damage, weapon.max_damage + 1);
+    let damage = max(1, roll + attacker.attack / 2 - defender.defense);
+    defender.take_damage(damage);
+
+    if defender.is_dead() {
+        AttackResult::Kill(damage)
+    } else {
+        AttackResult::Hit(damage)
+    }
+}
@@ -0,0 +1,276 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use util::{ Bound, Point };
+use terrain::Terrain;
+use fov::Explored;
+
+// Distances are kept in tenths of a step so the fleeing map can be scaled without
+// losing precision to integer division.
+pub static STEP: i32 = 10;
+// How much further than the chase map the fleeing map reaches. Anything over 1 makes
+// a cornered monster prefer running past its pursuer into open space.
+static FLEE_SCALE_TENTHS: i32 = 12;
+
+static DIRECTIONS: [(i32, i32), ..8] = [
+    (-1, -1), (0, -1), (1, -1),
+    (-1, 0), (1, 0),
+    (-1, 1), (0, 1), (1, 1)
+];
+
+struct Node {
+    distance: i32,
+    point: Point
+}
+
+// BinaryHeap is a max heap, so the ordering is reversed to pop the nearest node first.
+impl PartialEq for Node {
+    fn eq(&self, other: &Node) -> bool {
+        self.distance == other.distance
+    }
+}
+
+impl Eq for Node {}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Node) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Node) -> Ordering {
+        other.distance.cmp(&self.distance)
+    }
+}
+
+// The distance from every walkable cell to the nearest of a set of goals. Walking
+// downhill leads to a goal; walking uphill on a fleeing map leads away from them.
+pub struct DijkstraMap {
+    distances: Vec<Vec<Option<i32>>>,
+    bounds: Bound
+}
+
+impl DijkstraMap {
+    pub fn new(terrain: &Terrain, goals: &[Point]) -> DijkstraMap {
+        DijkstraMap::with_passable(terrain, goals, |point| terrain.is_passable(point))
+    }
+
+    // Like `new`, but only walks the cells `is_passable` allows, such as the ones the
+    // heroine has already explored.
+    pub fn with_passable(terrain: &Terrain, goals: &[Point], is_passable: |Point| -> bool) -> DijkstraMap {
+        let seeds: Vec<(Point, i32)> = goals.iter().map(|&goal| (goal, 0)).collect();
+        DijkstraMap::flood(terrain.bounds(), seeds, is_passable)
+    }
+
+    // Turns a map towards some goals into one that leads away from them. Rather than
+    // simply climbing uphill into the nearest dead end, fleeing monsters follow this
+    // map downhill and will slip past the goal if that's the way to open ground.
+    pub fn fleeing(&self, terrain: &Terrain) -> DijkstraMap {
+        let mut seeds: Vec<(Point, i32)> = vec![];
+        for point in self.bounds.cells() {
+            match self.distance(point) {
+                Some(distance) => seeds.push((point, -distance * FLEE_SCALE_TENTHS / 10)),
+                None => {}
+            }
+        }
+        DijkstraMap::flood(self.bounds, seeds, |point| terrain.is_passable(point))
+    }
+
+    fn flood(bounds: Bound, seeds: Vec<(Point, i32)>, is_passable: |Point| -> bool) -> DijkstraMap {
+        let mut map = DijkstraMap {
+            distances: Vec::from_elem(bounds.width() as uint, Vec::from_elem(bounds.height() as uint, None)),
+            bounds: bounds
+        };
+        let mut open = BinaryHeap::new();
+
+        for &(point, distance) in seeds.iter() {
+            if is_passable(point) && map.improve(point, distance) {
+                open.push(Node { distance: distance, point: point });
+            }
+        }
+
+        while let Some(node) = open.pop() {
+            // Skip entries left behind after a shorter way to this cell was found.
+            match map.distance(node.point) {
+                Some(distance) if distance < node.distance => continue,
+                _ => {}
+            }
+
+            for &(dx, dy) in DIRECTIONS.iter() {
+                let next = Point::new(node.point.x + dx, node.point.y + dy);
+                let distance = node.distance + STEP;
+                if is_passable(next) && map.improve(next, distance) {
+                    open.push(Node { distance: distance, point: next });
+                }
+            }
+        }
+
+        map
+    }
+
+    fn improve(&mut self, point: Point, distance: i32) -> bool {
+        let (x, y) = match self.bounds.grid_index(point) {
+            Some(index) => index,
+            None => return false
+        };
+        match self.distances[x][y] {
+            Some(known) if known <= distance => false,
+            _ => {
+                self.distances[x][y] = Some(distance);
+                true
+            }
+        }
+    }
+
+    // None for cells that can't reach any goal.
+    pub fn distance(&self, point: Point) -> Option<i32> {
+        match self.bounds.grid_index(point) {
+            Some((x, y)) => self.distances[x][y],
+            None => None
+        }
+    }
+
+    // The neighbouring cell that goes furthest downhill from `from`, skipping the
+    // blocked ones. None when every way on is uphill or blocked.
+    pub fn downhill(&self, from: Point, is_blocked: |Point| -> bool) -> Option<Point> {
+        let mut best = match self.distance(from) {
+            Some(distance) => distance,
+            None => return None
+        };
+        let mut step = None;
+        for &(dx, dy) in DIRECTIONS.iter() {
+            let next = Point::new(from.x + dx, from.y + dy);
+            match self.distance(next) {
+                Some(distance) if distance < best && !is_blocked(next) => {
+                    best = distance;
+                    step = Some(next);
+                },
+                _ => {}
+            }
+        }
+        step
+    }
+}
+
+// The maps monsters share to hunt or run from the heroine. They are only rebuilt
+// when she moves or the terrain changes, not once per monster.
+pub struct FlowMaps {
+    pub chase: DijkstraMap,
+    pub flee: DijkstraMap,
+    goal: Point,
+    revision: uint
+}
+
+impl FlowMaps {
+    pub fn new(terrain: &Terrain, goal: Point) -> FlowMaps {
+        let chase = DijkstraMap::new(terrain, &[goal]);
+        let flee = chase.fleeing(terrain);
+        FlowMaps { chase: chase, flee: flee, goal: goal, revision: terrain.revision() }
+    }
+
+    pub fn is_stale(&self, terrain: &Terrain, goal: Point) -> bool {
+        self.goal.x != goal.x || self.goal.y != goal.y || self.revision != terrain.revision()
+    }
+}
+
+// The way to the stairs over ground the heroine has explored, kept between trips
+// until the terrain, what she has explored or the stairs themselves change.
+pub struct TravelMap {
+    pub map: DijkstraMap,
+    stairs: Point,
+    terrain_revision: uint,
+    explored_revision: uint
+}
+
+impl TravelMap {
+    pub fn new(terrain: &Terrain, explored: &Explored, stairs: Point) -> TravelMap {
+        let map = DijkstraMap::with_passable(terrain, &[stairs], |point| terrain.is_passable(point) && explored.is_explored(point));
+        TravelMap {
+            map: map,
+            stairs: stairs,
+            terrain_revision: terrain.revision(),
+            explored_revision: explored.revision()
+        }
+    }
+
+    pub fn is_stale(&self, terrain: &Terrain, explored: &Explored, stairs: Point) -> bool {
+        self.stairs.x != stairs.x || self.stairs.y != stairs.y ||
+        self.terrain_revision != terrain.revision() ||
+        self.explored_revision != explored.revision()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ DijkstraMap, TravelMap, STEP };
+    use fov::Explored;
+    use terrain::{ Terrain, Tile };
+    use util::{ Bound, Point };
+
+    fn room() -> Terrain {
+        Terrain::new(Bound::new(0, 0, 11, 7))
+    }
+
+    #[test]
+    fn distances_count_steps_to_the_nearest_goal() {
+        let terrain = room();
+        let map = DijkstraMap::new(&terrain, &[Point::new(2, 2), Point::new(9, 2)]);
+        assert_eq!(map.distance(Point::new(2, 2)), Some(0));
+        assert_eq!(map.distance(Point::new(4, 4)), Some(2 * STEP));
+        assert_eq!(map.distance(Point::new(8, 5)), Some(3 * STEP));
+        assert_eq!(map.distance(Point::new(0, 0)), None);
+    }
+
+    #[test]
+    fn walls_make_the_way_longer() {
+        let mut terrain = room();
+        for y in range(1i32, 6) {
+            terrain.set(Point::new(5, y), Tile::Wall);
+        }
+        let map = DijkstraMap::new(&terrain, &[Point::new(2, 1)]);
+        // Around the bottom of the wall instead of straight through it.
+        assert_eq!(map.distance(Point::new(8, 1)), Some(10 * STEP));
+    }
+
+    #[test]
+    fn downhill_leads_to_the_goal_around_blocked_cells() {
+        let terrain = room();
+        let map = DijkstraMap::new(&terrain, &[Point::new(1, 3)]);
+        let step = map.downhill(Point::new(5, 3), |point| point.x == 4 && point.y == 3).unwrap();
+        assert_eq!(step.x, 4);
+        assert!(step.y != 3);
+        assert!(map.downhill(Point::new(1, 3), |_| false).is_none());
+    }
+
+    #[test]
+    fn fleeing_leads_away_from_the_goal() {
+        let terrain = room();
+        let chase = DijkstraMap::new(&terrain, &[Point::new(3, 3)]);
+        let flee = chase.fleeing(&terrain);
+        let from = Point::new(5, 3);
+        let step = flee.downhill(from, |_| false).unwrap();
+        assert!(chase.distance(step).unwrap() > chase.distance(from).unwrap());
+    }
+
+    #[test]
+    fn the_travel_map_is_kept_until_something_it_depends_on_changes() {
+        let mut terrain = room();
+        let mut explored = Explored::new(terrain.bounds());
+        let stairs = Point::new(9, 2);
+        explored.mark(stairs);
+        let travel = TravelMap::new(&terrain, &explored, stairs);
+        assert!(!travel.is_stale(&terrain, &explored, stairs));
+
+        // Somewhere she has already been adds nothing.
+        explored.mark(stairs);
+        assert!(!travel.is_stale(&terrain, &explored, stairs));
+        assert!(travel.is_stale(&terrain, &explored, Point::new(2, 2)));
+
+        explored.mark(Point::new(8, 2));
+        assert!(travel.is_stale(&terrain, &explored, stairs));
+        let travel = TravelMap::new(&terrain, &explored, stairs);
+        terrain.set(Point::new(5, 5), Tile::Water);
+        assert!(travel.is_stale(&terrain, &explored, stairs));
+    }
+}
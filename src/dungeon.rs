@@ -0,0 +1,213 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use util::{ Bound, Point };
+use actor::Actor;
+use action::Action;
+use game::Windows;
+use maps::Maps;
+use mapgen;
+use mapgen::{ Level, LevelSpec };
+use movement::MoveInfo;
+use terrain::Tile;
+
+pub struct Dungeon<'a> {
+    pub levels: Vec<Maps<'a>>,
+    pub depth: uint,
+    bounds: Bound,
+    seed: u32,
+    move_info: Rc<RefCell<MoveInfo>>
+}
+
+impl<'a> Dungeon<'a> {
+    pub fn new(move_info: Rc<RefCell<MoveInfo>>, first_level: Level, seed: u32) -> Dungeon<'a> {
+        let bounds = { move_info.borrow().deref().bounds };
+        let start = first_level.start;
+        let mut maps = Maps::new(move_info.clone(), first_level, None);
+        maps.pcs.push_actor(start, box Actor::heroine(move_info.clone()));
+        maps.refresh_heroine_fov();
+
+        Dungeon {
+            levels: vec![maps],
+            depth: 1,
+            bounds: bounds,
+            seed: seed,
+            move_info: move_info
+        }
+    }
+
+    // Puts a dungeon back together from levels loaded out of a save file.
+    pub fn restore(move_info: Rc<RefCell<MoveInfo>>, levels: Vec<Maps<'a>>, depth: uint, seed: u32) -> Dungeon<'a> {
+        let bounds = { move_info.borrow().deref().bounds };
+        let mut levels = levels;
+        levels[depth - 1].refresh_heroine_fov();
+        Dungeon {
+            levels: levels,
+            depth: depth,
+            bounds: bounds,
+            seed: seed,
+            move_info: move_info
+        }
+    }
+
+    pub fn generate_level(depth: uint, bounds: Bound, seed: u32) -> Level {
+        mapgen::generate(&LevelSpec::for_depth(depth, bounds, seed))
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    pub fn bounds(&self) -> Bound {
+        self.bounds
+    }
+
+    pub fn current(&mut self) -> &mut Maps<'a> {
+        &mut self.levels[self.depth - 1]
+    }
+
+    // Has the heroine do something and lets the rest of the world catch up if it took
+    // time. A turn spent on the stairs passes on the level she arrives on.
+    pub fn take_action(&mut self, action: Action, windows: &mut Windows) {
+        if self.perform(action, windows) {
+            self.current().end_turn(windows);
+        }
+    }
+
+    // Takes the stairs itself and leaves everything else to the current level, see `Maps::perform`.
+    pub fn perform(&mut self, action: Action, windows: &mut Windows) -> bool {
+        let position = match self.current().heroine() {
+            Some(heroine) => heroine.position,
+            None => return false
+        };
+        let tile = self.current().terrain.get(position);
+
+        match action {
+            Action::Descend => {
+                if tile != Some(Tile::StairsDown) {
+                    windows.messages.buffer_message("There are no stairs down here.");
+                    return false;
+                }
+                self.descend();
+                let msg = format!("You descend to depth {}.", self.depth);
+                windows.messages.buffer_message(msg.as_slice());
+                true
+            },
+            Action::Ascend => {
+                if tile != Some(Tile::StairsUp) {
+                    windows.messages.buffer_message("There are no stairs up here.");
+                    return false;
+                }
+                self.ascend();
+                let msg = format!("You climb up to depth {}.", self.depth);
+                windows.messages.buffer_message(msg.as_slice());
+                true
+            },
+            _ => self.current().perform(action, windows)
+        }
+    }
+
+    pub fn descend(&mut self) {
+        if self.depth == self.levels.len() {
+            let level = Dungeon::generate_level(self.depth + 1, self.bounds, self.seed);
+            let start = level.start;
+            let maps = Maps::new(self.move_info.clone(), level, Some(start));
+            self.levels.push(maps);
+        }
+
+        let arrival = match self.levels[self.depth].up_stairs {
+            Some(point) => point,
+            None => self.levels[self.depth].down_stairs
+        };
+        self.move_heroine(self.depth + 1, arrival);
+    }
+
+    pub fn ascend(&mut self) {
+        if self.depth <= 1 {
+            return;
+        }
+
+        let arrival = self.levels[self.depth - 2].down_stairs;
+        self.move_heroine(self.depth - 1, arrival);
+    }
+
+    fn move_heroine(&mut self, depth: uint, arrival: Point) {
+        let char_location = { self.move_info.borrow().deref().char_location };
+        let heroine = self.current().pcs.remove_actor(char_location);
+        match heroine {
+            Some(mut heroine) => {
+                heroine.position = arrival;
+                // Only what she builds up here counts against the level's own actors.
+                heroine.earned = 0;
+                // Whoever wandered onto the stairs makes way for her.
+                self.levels[depth - 1].clear_cell(arrival);
+                self.levels[depth - 1].pcs.push_actor(arrival, heroine);
+            },
+            None => {}
+        }
+
+        self.depth = depth;
+        {
+            let mut move_info = self.move_info.borrow_mut();
+            move_info.deref_mut().char_location = arrival
+        };
+        self.levels[depth - 1].refresh_heroine_fov();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::Dungeon;
+    use action::Action;
+    use actor::Actor;
+    use game::test::windows;
+    use mapgen::Level;
+    use movement::MoveInfo;
+    use scheduler::ACTION_COST;
+    use terrain::Terrain;
+    use util::{ Bound, Point };
+
+    fn small_dungeon(exit: Point) -> (Dungeon<'static>, Rc<RefCell<MoveInfo>>) {
+        let bounds = Bound::new(0, 0, 19, 9);
+        let move_info = Rc::new(RefCell::new(MoveInfo::new(bounds, Point::new(2, 2))));
+        let level = Level {
+            terrain: Terrain::new(bounds),
+            start: Point::new(2, 2),
+            exit: exit,
+            spawns: vec![],
+            items: vec![]
+        };
+        (Dungeon::new(move_info.clone(), level, 1234), move_info)
+    }
+
+    #[test]
+    fn whoever_is_on_the_stairs_makes_way_for_her() {
+        let (mut dungeon, move_info) = small_dungeon(Point::new(17, 7));
+        dungeon.descend();
+        let stairs = Point::new(17, 7);
+        dungeon.levels[0].enemies.push_actor(stairs, box Actor::kobold(17, 7, move_info.clone()));
+
+        dungeon.ascend();
+        let maps = dungeon.current();
+        assert!(maps.pcs.actor_at(stairs).is_some());
+        assert!(maps.enemies.actor_at(stairs).is_none());
+        assert_eq!(maps.enemies.ids().len(), 1);
+    }
+
+    #[test]
+    fn taking_the_stairs_takes_a_turn() {
+        let (mut dungeon, _) = small_dungeon(Point::new(3, 2));
+        let mut windows = windows();
+        dungeon.take_action(Action::Move(Point::new(1, 0)), &mut windows);
+        dungeon.take_action(Action::Descend, &mut windows);
+
+        assert_eq!(dungeon.depth, 2);
+        // She has spent the turn and earned it back on the level she arrived on.
+        let heroine = dungeon.current().heroine().unwrap();
+        assert_eq!(heroine.energy, ACTION_COST);
+        assert_eq!(heroine.earned, ACTION_COST);
+    }
+}
@@ -0,0 +1,253 @@
+use util::{ Bound, Point };
+use terrain::Terrain;
+
+// Symmetric shadowcasting: every cell visible from A can also see A, which keeps
+// monster sight and the heroine's sight in agreement.
+
+pub struct Fov {
+    visible: Vec<Vec<bool>>,
+    bounds: Bound
+}
+
+enum Quadrant {
+    North,
+    South,
+    East,
+    West
+}
+
+struct Row {
+    depth: i32,
+    // Slopes are kept as fractions (numerator, denominator) so scans stay exact.
+    start_slope: (i32, i32),
+    end_slope: (i32, i32)
+}
+
+impl Row {
+    fn min_col(&self) -> i32 {
+        let (num, den) = self.start_slope;
+        floor_div(2 * self.depth * num + den, 2 * den)
+    }
+
+    fn max_col(&self) -> i32 {
+        let (num, den) = self.end_slope;
+        -floor_div(-(2 * self.depth * num - den), 2 * den)
+    }
+
+    fn next(&self) -> Row {
+        Row { depth: self.depth + 1, start_slope: self.start_slope, end_slope: self.end_slope }
+    }
+
+    fn is_symmetric(&self, col: i32) -> bool {
+        let (start_num, start_den) = self.start_slope;
+        let (end_num, end_den) = self.end_slope;
+        col * start_den >= self.depth * start_num && col * end_den <= self.depth * end_num
+    }
+}
+
+impl Quadrant {
+    fn transform(&self, origin: Point, depth: i32, col: i32) -> Point {
+        match *self {
+            Quadrant::North => Point::new(origin.x + col, origin.y - depth),
+            Quadrant::South => Point::new(origin.x + col, origin.y + depth),
+            Quadrant::East => Point::new(origin.x + depth, origin.y + col),
+            Quadrant::West => Point::new(origin.x - depth, origin.y + col)
+        }
+    }
+}
+
+impl Fov {
+    pub fn new(bounds: Bound) -> Fov {
+        let visible = Vec::from_elem(bounds.width() as uint, Vec::from_elem(bounds.height() as uint, false));
+        Fov { visible: visible, bounds: bounds }
+    }
+
+    pub fn compute(terrain: &Terrain, origin: Point, radius: i32) -> Fov {
+        let mut fov = Fov::new(terrain.bounds());
+        fov.reveal(origin);
+
+        for quadrant in [Quadrant::North, Quadrant::South, Quadrant::East, Quadrant::West].iter() {
+            let first_row = Row { depth: 1, start_slope: (-1, 1), end_slope: (1, 1) };
+            fov.scan(terrain, origin, radius, quadrant, first_row);
+        }
+
+        fov
+    }
+
+    pub fn is_visible(&self, point: Point) -> bool {
+        match self.bounds.grid_index(point) {
+            Some((x, y)) => self.visible[x][y],
+            None => false
+        }
+    }
+
+    fn reveal(&mut self, point: Point) {
+        match self.bounds.grid_index(point) {
+            Some((x, y)) => self.visible[x][y] = true,
+            None => {}
+        }
+    }
+
+    fn scan(&mut self, terrain: &Terrain, origin: Point, radius: i32, quadrant: &Quadrant, row: Row) {
+        if row.depth > radius {
+            return;
+        }
+
+        let mut row = row;
+        let mut prev_is_wall: Option<bool> = None;
+
+        for col in range(row.min_col(), row.max_col() + 1) {
+            let point = quadrant.transform(origin, row.depth, col);
+            let is_wall = terrain.blocks_sight(point);
+            let in_radius = row.depth * row.depth + col * col <= radius * radius;
+
+            if in_radius && (is_wall || row.is_symmetric(col)) {
+                self.reveal(point);
+            }
+
+            match prev_is_wall {
+                Some(true) if !is_wall => row.start_slope = (2 * col - 1, 2 * row.depth),
+                Some(false) if is_wall => {
+                    let mut next_row = row.next();
+                    next_row.end_slope = (2 * col - 1, 2 * row.depth);
+                    self.scan(terrain, origin, radius, quadrant, next_row);
+                },
+                _ => {}
+            }
+            prev_is_wall = Some(is_wall);
+        }
+
+        match prev_is_wall {
+            Some(false) => self.scan(terrain, origin, radius, quadrant, row.next()),
+            _ => {}
+        }
+    }
+}
+
+pub struct Explored {
+    seen: Vec<Vec<bool>>,
+    bounds: Bound,
+    // Bumped whenever a cell is explored for the first time, like `Terrain::revision`.
+    revision: uint
+}
+
+impl Explored {
+    pub fn new(bounds: Bound) -> Explored {
+        let seen = Vec::from_elem(bounds.width() as uint, Vec::from_elem(bounds.height() as uint, false));
+        Explored { seen: seen, bounds: bounds, revision: 0 }
+    }
+
+    pub fn revision(&self) -> uint {
+        self.revision
+    }
+
+    pub fn remember(&mut self, fov: &Fov) {
+        for (x, x_iter) in fov.visible.iter().enumerate() {
+            for (y, &visible) in x_iter.iter().enumerate() {
+                if visible && !self.seen[x][y] {
+                    self.seen[x][y] = true;
+                    self.revision += 1;
+                }
+            }
+        }
+    }
+
+    pub fn mark(&mut self, point: Point) {
+        match self.bounds.grid_index(point) {
+            Some((x, y)) if !self.seen[x][y] => {
+                self.seen[x][y] = true;
+                self.revision += 1;
+            },
+            _ => {}
+        }
+    }
+
+    pub fn is_explored(&self, point: Point) -> bool {
+        match self.bounds.grid_index(point) {
+            Some((x, y)) => self.seen[x][y],
+            None => false
+        }
+    }
+}
+
+fn floor_div(a: i32, b: i32) -> i32 {
+    let quotient = a / b;
+    if (a % b != 0) && ((a < 0) != (b < 0)) { quotient - 1 } else { quotient }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Fov;
+    use util::{ Bound, Point };
+    use terrain::{ Terrain, Tile };
+
+    fn open_room() -> Terrain {
+        Terrain::new(Bound::new(0, 0, 20, 20))
+    }
+
+    #[test]
+    fn origin_is_always_visible() {
+        let terrain = open_room();
+        let fov = Fov::compute(&terrain, Point::new(5, 5), 0);
+        assert!(fov.is_visible(Point::new(5, 5)));
+        assert!(!fov.is_visible(Point::new(6, 5)));
+    }
+
+    #[test]
+    fn open_room_is_visible_within_radius() {
+        let terrain = open_room();
+        let fov = Fov::compute(&terrain, Point::new(10, 10), 4);
+        assert!(fov.is_visible(Point::new(14, 10)));
+        assert!(fov.is_visible(Point::new(10, 6)));
+        assert!(!fov.is_visible(Point::new(15, 10)));
+        assert!(!fov.is_visible(Point::new(14, 14)));
+    }
+
+    #[test]
+    fn walls_block_sight_but_are_visible() {
+        let mut terrain = open_room();
+        for y in range(1i32, 20) {
+            terrain.set(Point::new(10, y), Tile::Wall);
+        }
+        let fov = Fov::compute(&terrain, Point::new(5, 10), 15);
+        assert!(fov.is_visible(Point::new(10, 10)));
+        assert!(!fov.is_visible(Point::new(11, 10)));
+        assert!(!fov.is_visible(Point::new(15, 12)));
+    }
+
+    #[test]
+    fn pillar_casts_a_shadow() {
+        let mut terrain = open_room();
+        terrain.set(Point::new(8, 10), Tile::Wall);
+        let fov = Fov::compute(&terrain, Point::new(5, 10), 15);
+        assert!(fov.is_visible(Point::new(8, 10)));
+        assert!(!fov.is_visible(Point::new(9, 10)));
+        assert!(!fov.is_visible(Point::new(12, 10)));
+        assert!(fov.is_visible(Point::new(12, 5)));
+    }
+
+    #[test]
+    fn visibility_is_symmetric() {
+        let mut terrain = open_room();
+        let pillars = [Point::new(7, 7), Point::new(12, 9), Point::new(9, 14), Point::new(14, 14), Point::new(5, 12)];
+        for &pillar in pillars.iter() {
+            terrain.set(pillar, Tile::Wall);
+        }
+
+        let mut views: Vec<(Point, Fov)> = vec![];
+        for x in range(1i32, 20) {
+            for y in range(1i32, 20) {
+                let point = Point::new(x, y);
+                if terrain.is_passable(point) {
+                    views.push((point, Fov::compute(&terrain, point, 8)));
+                }
+            }
+        }
+
+        for &(a, ref from_a) in views.iter() {
+            for &(b, ref from_b) in views.iter() {
+                assert_eq!(from_a.is_visible(b), from_b.is_visible(a));
+            }
+        }
+    }
+}
@@ -1,25 +1,38 @@
 use std::cell::RefCell;
 use std::rc::Rc;
+use std::rand::{ task_rng, Rng };
 
-use util::Bound;
+use util::{ Bound, Point };
 use rendering::RenderingComponent;
 use window::{
     WindowComponent,
     TcodStatsWindowComponent,
     TcodInputWindowComponent,
     TcodMessagesWindowComponent,
-    TcodMapWindowComponent
+    TcodMapWindowComponent,
+    TcodInventoryWindowComponent
 };
-use input::{ KeyboardInput, KeyCode };
-use input::Key::{ SpecialKey, Printable };
+use input::KeyboardInput;
+use input::Key::Printable;
 use maps::Maps;
 use movement::MoveInfo;
+use dungeon::Dungeon;
+use camera::Camera;
+use item::{ ItemKind, SLOTS };
+use action::{ Action, direction_from_key };
+use save::{ SavedGame, Store };
+use replay::Replay;
+
+// The size of the whole screen, for building a rendering backend to hand to `Game::new`.
+pub static SCREEN_BOUNDS: Bound = Bound { min: Point { x: 0, y: 0 }, max: Point { x: 99, y: 61 } };
 
 pub struct Windows<'a> {
     pub stats: Box<WindowComponent + 'a>,
     pub map: Box<WindowComponent + 'a>,
     pub input: Box<WindowComponent + 'a>,
-    pub messages: Box<WindowComponent + 'a>
+    pub messages: Box<WindowComponent + 'a>,
+    // Only drawn by the states that overlay it on the map.
+    pub inventory: Box<WindowComponent + 'a>
 }
 
 impl<'a> Windows<'a> {
@@ -39,61 +52,127 @@ impl<'a> Windows<'a> {
 
 pub trait GameState {
     fn new() -> Self;
-    fn update(&mut self, maps: &mut Maps, windows: &mut Windows, Rc<RefCell<MoveInfo>>);
+    fn update(&mut self, dungeon: &mut Dungeon, windows: &mut Windows, Rc<RefCell<MoveInfo>>);
     fn should_update_state(&self) -> bool;
+    // What save files call the state the game was left in.
+    fn name(&self) -> &'static str;
 
     fn enter(&self, &mut Windows) {}
     fn exit(&self) {}
+    fn should_exit_game(&self) -> bool { false }
+
+    // Lets a state hand over to another one straight away, without waiting for a key
+    // to pick the next state.
+    fn next_state(&mut self) -> Option<Box<GameState + 'static>> { None }
+
+    fn render_overlay(&mut self, _: &mut Box<RenderingComponent>, _: &mut Windows) {}
 
     fn render(
         &mut self,
         renderer: &mut Box<RenderingComponent>,
-        maps: &mut Maps,
+        maps: &Maps,
+        camera: &Camera,
         windows: &mut Windows
     ) {
         renderer.before_render_new_frame();
-        let mut all_windows = windows.all_windows();
-
-        for window in all_windows.iter_mut() {
-            renderer.attach_window(*window);
+        {
+            let mut all_windows = windows.all_windows();
+            for window in all_windows.iter_mut() {
+                renderer.attach_window(*window);
+            }
         }
 
-        maps.render(renderer);
+        maps.render(renderer, camera);
+        self.render_overlay(renderer, windows);
         renderer.after_render_new_frame();
     }
 }
 
-pub struct MovementGameState;
+pub struct MovementGameState {
+    should_exit_game: bool
+}
 
 impl GameState for MovementGameState {
-    fn new() -> MovementGameState { MovementGameState }
+    fn new() -> MovementGameState {
+        MovementGameState { should_exit_game: false }
+    }
 
     fn should_update_state(&self) -> bool { true }
 
-    fn update(&mut self, maps: &mut Maps, windows: &mut Windows, move_info: Rc<RefCell<MoveInfo>>) {
+    fn name(&self) -> &'static str { "movement" }
+
+    fn should_exit_game(&self) -> bool {
+        self.should_exit_game
+    }
+
+    fn update(&mut self, dungeon: &mut Dungeon, windows: &mut Windows, move_info: Rc<RefCell<MoveInfo>>) {
+        let last_keypress = { move_info.borrow().deref().last_keypress };
+        let key = match last_keypress {
+            Some(ks) => ks.key,
+            None => return
+        };
+
+        match Action::from_key(key) {
+            Some(Action::Quit) => self.should_exit_game = true,
+            Some(action) => dungeon.take_action(action, windows),
+            // Anything else, like Shift or a key that just switched states, is not an action.
+            None => match key {
+                Printable(c) if ItemKind::from_key(c).is_some() => {
+                    let msg = format!("You don't have a {}.", ItemKind::from_key(c).unwrap().name());
+                    windows.messages.buffer_message(msg.as_slice());
+                },
+                _ => {}
+            }
+        }
+    }
+}
+
+pub struct GameOverGameState {
+    should_exit_game: bool
+}
+
+impl GameState for GameOverGameState {
+    fn new() -> GameOverGameState {
+        GameOverGameState { should_exit_game: false }
+    }
+
+    fn should_update_state(&self) -> bool { false }
+
+    fn name(&self) -> &'static str { "game_over" }
+
+    fn should_exit_game(&self) -> bool {
+        self.should_exit_game
+    }
+
+    fn enter(&self, windows: &mut Windows) {
+        windows.input.flush_buffer();
+        windows.input.buffer_message("You have died! [Press any key to exit]");
+    }
+
+    fn update(&mut self, _: &mut Dungeon, _: &mut Windows, move_info: Rc<RefCell<MoveInfo>>) {
         let last_keypress = { move_info.borrow().deref().last_keypress };
         match last_keypress {
-            Some(ks) => {
-                match ks.key {
-                    SpecialKey(KeyCode::Shift) => {},
-                    _ => { maps.update(windows); }
-                }
-            },
-            _ => {}
+            Some(_) => self.should_exit_game = true,
+            None => {}
         }
     }
 }
 
 pub struct AttackInputGameState {
     should_update_state: bool,
-    weapon: String,
+    weapon: ItemKind,
+    // Throw the item itself instead of using it for what it's made for.
+    throwing: bool,
+    is_open: bool
 }
 
 impl GameState for AttackInputGameState {
     fn new() -> AttackInputGameState {
         AttackInputGameState {
             should_update_state: false,
-            weapon: "".to_string()
+            weapon: ItemKind::HeroicSword,
+            throwing: false,
+            is_open: false
         }
     }
 
@@ -101,106 +180,351 @@ impl GameState for AttackInputGameState {
         self.should_update_state
     }
 
+    fn name(&self) -> &'static str { "attack_input" }
+
     fn enter(&self, windows: &mut Windows) {
         windows.input.flush_buffer();
-        let mut msg = "Which direction would you like to attack with".to_string();
-        msg.push_str(self.weapon.as_slice());
-        msg.push_str("? [Press an arrow key]");
-        windows.input.buffer_message(msg.as_slice());
+        let prompt = if self.throwing { self.weapon.throw_prompt() } else { self.weapon.prompt() };
+        windows.input.buffer_message(prompt.as_slice());
     }
 
-    fn update(&mut self, maps: &mut Maps, windows: &mut Windows, move_info: Rc<RefCell<MoveInfo>>) {
+    fn update(&mut self, dungeon: &mut Dungeon, windows: &mut Windows, move_info: Rc<RefCell<MoveInfo>>) {
         let last_keypress = { move_info.borrow().deref().last_keypress };
-        match last_keypress {
-            Some(ks) => {
-                let mut msg = "You attack ".to_string();
-                let mut point = { move_info.borrow().deref().char_location };
-                match ks.key {
-                    SpecialKey(KeyCode::Up) => {
-                        point = point.offset_y(-1);
-                        msg.push_str("up");
-                        self.should_update_state = true;
-                    },
-                    SpecialKey(KeyCode::Down) => {
-                        point = point.offset_y(1);
-                        msg.push_str("down");
-                        self.should_update_state = true;
-                    },
-                    SpecialKey(KeyCode::Left) => {
-                        point = point.offset_x(-1);
-                        msg.push_str("left");
-                        self.should_update_state = true;
-                    },
-                    SpecialKey(KeyCode::Right) => {
-                        point = point.offset_x(1);
-                        msg.push_str("right");
-                        self.should_update_state = true;
-                    },
-                    _ => {}
+        let key = match last_keypress {
+            Some(ks) => ks.key,
+            None => return
+        };
+
+        // The weapon's hotkey that opened the prompt isn't a direction or a cancel.
+        if !self.is_open {
+            self.is_open = true;
+            return;
+        }
+
+        self.should_update_state = true;
+        match direction_from_key(key) {
+            Some(direction) => {
+                let action = if self.throwing {
+                    Action::Throw(self.weapon, direction)
+                } else {
+                    Action::Use(self.weapon, direction)
+                };
+                dungeon.take_action(action, windows);
+            },
+            // Like the inventory, any other key, Escape included, backs out.
+            None => windows.input.flush_buffer()
+        }
+    }
+}
+
+pub enum InventoryAction {
+    Use,
+    Equip,
+    Throw,
+    Drop
+}
+
+pub struct InventoryGameState {
+    should_update_state: bool,
+    next_state: Option<Box<GameState + 'static>>,
+    // Set up front when the screen is opened to drop something.
+    action: Option<InventoryAction>,
+    selected: Option<uint>,
+    is_open: bool
+}
+
+impl InventoryGameState {
+    fn selected_item(&self, dungeon: &mut Dungeon) -> Option<ItemKind> {
+        match (self.selected, dungeon.current().heroine()) {
+            (Some(index), Some(heroine)) => heroine.inventory.get(index),
+            _ => None
+        }
+    }
+
+    fn is_selected_equipped(&self, dungeon: &mut Dungeon) -> bool {
+        match (self.selected, dungeon.current().heroine()) {
+            (Some(index), Some(heroine)) => heroine.inventory.is_equipped(index),
+            _ => false
+        }
+    }
+
+    fn refresh(&self, dungeon: &mut Dungeon, windows: &mut Windows) {
+        let mut lines: Vec<String> = vec![];
+        match dungeon.current().heroine() {
+            Some(heroine) => {
+                let inventory = &heroine.inventory;
+                lines.push(format!("Inventory ({}/{})", inventory.len(), inventory.capacity));
+                lines.push(String::new());
+                if inventory.len() == 0 {
+                    lines.push("You aren't carrying anything.".to_string());
                 }
+                lines.extend(inventory.describe().into_iter());
+            },
+            None => {}
+        }
+        windows.inventory.set_lines(lines.as_slice());
+
+        let prompt = match (self.selected_item(dungeon), self.action) {
+            (Some(kind), _) => {
+                let equip = if self.is_selected_equipped(dungeon) { "take off (e)" } else { "(e)quip" };
+                format!("(u)se, {}, (t)hrow or (d)rop your {}? [Any other key to cancel]", equip, kind.name())
+            },
+            (None, Some(InventoryAction::Drop)) =>
+                "Drop which item? [Press its letter, any other key to close]".to_string(),
+            (None, _) =>
+                "Select an item [Press its letter, any other key to close]".to_string()
+        };
+        windows.input.flush_buffer();
+        windows.input.buffer_message(prompt.as_slice());
+    }
+
+    fn close(&mut self, windows: &mut Windows) {
+        windows.input.flush_buffer();
+        self.should_update_state = true;
+    }
+
+    fn aim(&mut self, kind: ItemKind, throwing: bool) {
+        let mut state: Box<AttackInputGameState> = box GameState::new();
+        state.weapon = kind;
+        state.throwing = throwing;
+        // Handed over straight away, so there's no opening key to skip.
+        state.is_open = true;
+        self.next_state = Some(state as Box<GameState>);
+    }
+
+    fn perform(&mut self, action: InventoryAction, dungeon: &mut Dungeon, windows: &mut Windows) {
+        let (index, kind) = match (self.selected, self.selected_item(dungeon)) {
+            (Some(index), Some(kind)) => (index, kind),
+            _ => return self.close(windows)
+        };
+
+        match action {
+            InventoryAction::Use if !kind.is_aimed() => {
+                let msg = format!("Your {} only helps while it's equipped.", kind.name());
+                windows.messages.buffer_message(msg.as_slice());
+            },
+            InventoryAction::Use => return self.aim(kind, false),
+            InventoryAction::Throw => return self.aim(kind, true),
+            InventoryAction::Equip => dungeon.take_action(Action::Equip(index), windows),
+            InventoryAction::Drop => dungeon.take_action(Action::Drop(index), windows)
+        }
+        self.close(windows);
+    }
+}
 
-                if self.should_update_state {
-                    match maps.enemy_at(point) {
-                        Some(_) => {
-                            msg.push_str(" with your ");
-                            msg.push_str(self.weapon.as_slice());
-                            msg.push_str("!");
-                            windows.messages.buffer_message(msg.as_slice());
-                        },
-                        None => {
-                            windows.messages.buffer_message("No enemy in that direction!");
-                        }
+impl GameState for InventoryGameState {
+    fn new() -> InventoryGameState {
+        InventoryGameState {
+            should_update_state: false,
+            next_state: None,
+            action: None,
+            selected: None,
+            is_open: false
+        }
+    }
+
+    fn should_update_state(&self) -> bool {
+        self.should_update_state
+    }
+
+    fn name(&self) -> &'static str { "inventory" }
+
+    fn next_state(&mut self) -> Option<Box<GameState + 'static>> {
+        self.next_state.take()
+    }
+
+    fn update(&mut self, dungeon: &mut Dungeon, windows: &mut Windows, move_info: Rc<RefCell<MoveInfo>>) {
+        let last_keypress = { move_info.borrow().deref().last_keypress };
+        let key = match last_keypress {
+            Some(ks) => ks.key,
+            None => return
+        };
+
+        // The key that opened the screen isn't a selection.
+        if !self.is_open {
+            self.is_open = true;
+            return self.refresh(dungeon, windows);
+        }
+
+        match (self.selected, key) {
+            (None, Printable(c)) if c >= 'a' && c <= 'z' => {
+                self.selected = Some((c as u8 - b'a') as uint);
+                if self.selected_item(dungeon).is_none() {
+                    self.selected = None;
+                    windows.messages.buffer_message("You don't have that item.");
+                    return self.refresh(dungeon, windows);
+                }
+                match self.action {
+                    Some(action) => self.perform(action, dungeon, windows),
+                    None => self.refresh(dungeon, windows)
+                }
+            },
+            (Some(_), Printable('u')) => self.perform(InventoryAction::Use, dungeon, windows),
+            (Some(_), Printable('e')) => self.perform(InventoryAction::Equip, dungeon, windows),
+            (Some(_), Printable('t')) => self.perform(InventoryAction::Throw, dungeon, windows),
+            (Some(_), Printable('d')) => self.perform(InventoryAction::Drop, dungeon, windows),
+            _ => self.close(windows)
+        }
+    }
+
+    fn render_overlay(&mut self, renderer: &mut Box<RenderingComponent>, windows: &mut Windows) {
+        renderer.attach_window(&mut windows.inventory);
+    }
+}
+
+// Asks whether to pick up the saved game before anything else happens.
+pub struct ResumeGameState {
+    should_update_state: bool,
+    next_state: Option<Box<GameState + 'static>>,
+    store: Store
+}
+
+impl ResumeGameState {
+    fn with_store(store: Store) -> ResumeGameState {
+        ResumeGameState { should_update_state: false, next_state: None, store: store }
+    }
+
+    fn resume(&mut self, dungeon: &mut Dungeon, windows: &mut Windows, move_info: Rc<RefCell<MoveInfo>>) -> Result<(), String> {
+        let saved = try!(self.store.read());
+        *dungeon = try!(saved.restore(move_info));
+        windows.messages.set_lines(saved.messages.as_slice());
+
+        // Only the game over screen is worth coming back to. The map screen keeps nothing
+        // of its own, and the inventory and aiming prompts only hold an action she was
+        // still choosing, which hadn't cost her anything yet; so the game picks up on the
+        // map and she chooses again. The camera simply follows her back.
+        if saved.state.as_slice() == "game_over" {
+            let gs: Box<GameOverGameState> = box GameState::new();
+            self.next_state = Some(gs as Box<GameState>);
+        }
+        Ok(())
+    }
+}
+
+impl GameState for ResumeGameState {
+    fn new() -> ResumeGameState {
+        ResumeGameState::with_store(Store::default())
+    }
+
+    fn should_update_state(&self) -> bool {
+        self.should_update_state
+    }
+
+    fn name(&self) -> &'static str { "resume" }
+
+    fn next_state(&mut self) -> Option<Box<GameState + 'static>> {
+        self.next_state.take()
+    }
+
+    fn enter(&self, windows: &mut Windows) {
+        windows.input.flush_buffer();
+        windows.input.buffer_message("Resume your saved game? [y/n]");
+    }
+
+    fn update(&mut self, dungeon: &mut Dungeon, windows: &mut Windows, move_info: Rc<RefCell<MoveInfo>>) {
+        let last_keypress = { move_info.borrow().deref().last_keypress };
+        let key = match last_keypress {
+            Some(ks) => ks.key,
+            None => return
+        };
+
+        match key {
+            Printable('y') => {
+                match self.resume(dungeon, windows, move_info) {
+                    Ok(()) => {
+                        let msg = format!("Welcome back to depth {}.", dungeon.depth);
+                        windows.messages.buffer_message(msg.as_slice());
+                    },
+                    Err(e) => {
+                        let msg = format!("Your save couldn't be loaded: {}.", e);
+                        windows.messages.buffer_message(msg.as_slice());
                     }
                 }
             },
-            _ => {}
+            Printable('n') => {
+                self.store.delete();
+                windows.messages.buffer_message("You set out on a new adventure.");
+            },
+            _ => return
         }
+        windows.input.flush_buffer();
+        self.should_update_state = true;
     }
 }
 
 pub struct Game<'a> {
     pub window_bounds: Bound,
     pub exit: bool,
+    pub is_game_over: bool,
     pub rendering_component: Box<RenderingComponent + 'a>,
     pub game_state: Box<GameState + 'a>,
     pub windows: Windows<'a>,
-    pub maps: Maps<'a>,
-    pub move_info: Rc<RefCell<MoveInfo>>
+    pub dungeon: Dungeon<'a>,
+    pub camera: Camera,
+    pub move_info: Rc<RefCell<MoveInfo>>,
+    // Every key pressed since the game started from its seed. None once a saved game
+    // has been resumed, since that can't be replayed from the seed alone.
+    pub recording: Option<Replay>,
+    pub store: Store
 }
 
 impl<'a> Game<'a> {
-    pub fn new() -> Game<'a> {
-        let total_bounds   = Bound::new(0,  0, 99, 61);
+    // Without a seed every game is a new one; passing the seed of an earlier game
+    // rebuilds its dungeon and, given the same keypresses, everything that happens in it.
+    pub fn new(rendering_component: Box<RenderingComponent + 'a>, seed: Option<u32>, store: Store) -> Game<'a> {
+        let seed = match seed {
+            Some(seed) => seed,
+            None => task_rng().gen()
+        };
+        let mut game = Game::with_seed(rendering_component, seed, store);
+        if game.store.exists() {
+            let gs = box ResumeGameState::with_store(game.store.clone());
+            game.game_state = gs as Box<GameState>;
+            game.game_state.enter(&mut game.windows);
+        }
+        game
+    }
+
+    // Starts a new game straight away, without offering to resume a saved one.
+    pub fn with_seed(rendering_component: Box<RenderingComponent + 'a>, seed: u32, store: Store) -> Game<'a> {
         let stats_bounds   = Bound::new(79, 0, 99, 49);
         let input_bounds   = Bound::new(0, 50, 99, 52);
         let message_bounds = Bound::new(0, 53, 99, 61);
         let map_bounds     = Bound::new(0,  0, 78, 49);
+        let inventory_bounds = Bound::new(20, 10, 58, 24);
+        let world_bounds   = Bound::new(0,  0, 239, 119);
 
-        let rc: Box<RenderingComponent> = box RenderingComponent::new(total_bounds);
         let sw: Box<TcodStatsWindowComponent> = box WindowComponent::new(stats_bounds);
         let iw: Box<TcodInputWindowComponent> = box WindowComponent::new(input_bounds);
         let mw: Box<TcodMessagesWindowComponent> = box WindowComponent::new(message_bounds);
         let maw: Box<TcodMapWindowComponent> = box WindowComponent::new(map_bounds);
+        let inw: Box<TcodInventoryWindowComponent> = box WindowComponent::new(inventory_bounds);
 
         let windows = Windows {
             input: iw,
             messages: mw,
             map: maw,
-            stats: sw
+            stats: sw,
+            inventory: inw
         };
 
-        let move_info = Rc::new(RefCell::new(MoveInfo::new(map_bounds)));
+        let level = Dungeon::generate_level(1, world_bounds, seed);
+        let move_info = Rc::new(RefCell::new(MoveInfo::with_seed(world_bounds, level.start, seed)));
         let gs: Box<MovementGameState> = box GameState::new();
-        let maps = Maps::new(move_info.clone());
+        let dungeon = Dungeon::new(move_info.clone(), level, seed);
 
         Game {
             exit: false,
-            window_bounds: total_bounds,
-            rendering_component: rc,
+            is_game_over: false,
+            window_bounds: SCREEN_BOUNDS,
+            rendering_component: rendering_component,
             windows: windows,
             game_state: gs,
-            maps: maps,
-            move_info: move_info
+            dungeon: dungeon,
+            camera: Camera::new(map_bounds, world_bounds),
+            move_info: move_info,
+            recording: Some(Replay::new(seed)),
+            store: store
         }
     }
 
@@ -211,11 +535,84 @@ impl<'a> Game<'a> {
             self.game_state.enter(&mut self.windows);
         }
 
-        self.game_state.update(&mut self.maps, &mut self.windows, self.move_info.clone());
+        let was_resuming = self.game_state.name() == "resume";
+        self.game_state.update(&mut self.dungeon, &mut self.windows, self.move_info.clone());
+        if was_resuming && self.game_state.should_update_state() {
+            // Declining throws the save away; keeping it means the game no longer
+            // follows from its seed. Either way the answer itself isn't part of the game.
+            self.recording = if self.store.exists() { None } else { Some(Replay::new(self.dungeon.seed())) };
+        }
+
+        match self.game_state.next_state() {
+            Some(state) => {
+                self.game_state.exit();
+                self.game_state = state;
+                self.game_state.enter(&mut self.windows);
+            },
+            None => {}
+        }
+
+        if self.game_state.should_exit_game() {
+            self.exit = true;
+        } else if self.dungeon.current().is_heroine_dead() && !self.is_game_over {
+            let gs: Box<GameOverGameState> = box GameState::new();
+            self.game_state = gs as Box<GameState>;
+            self.game_state.enter(&mut self.windows);
+            self.is_game_over = true;
+        }
+    }
+
+    // Writes the game out for the next launch, or throws the save away once she has died.
+    pub fn save(&mut self) -> Result<(), String> {
+        if self.dungeon.current().is_heroine_dead() {
+            self.store.delete();
+            return Ok(());
+        }
+        // Nothing has happened yet that the save on disk doesn't already have.
+        if self.game_state.name() == "resume" {
+            return Ok(());
+        }
+
+        let messages = self.windows.messages.get_messages().iter().map(|message| (**message).clone()).collect();
+        let saved = SavedGame::new(&self.dungeon, self.move_info.clone(), messages, self.game_state.name());
+        self.store.write(&saved)
     }
 
     pub fn render(&mut self) {
-        self.game_state.render(&mut self.rendering_component, &mut self.maps, &mut self.windows);
+        let char_location = { self.move_info.borrow().deref().char_location };
+        self.camera.follow(char_location);
+        self.update_stats_window();
+        self.game_state.render(&mut self.rendering_component, self.dungeon.current(), &self.camera, &mut self.windows);
+    }
+
+    fn update_stats_window(&mut self) {
+        let mut lines = vec![
+            format!("Seed: {}", self.dungeon.seed()),
+            format!("Depth: {}", self.dungeon.depth)
+        ];
+        match self.dungeon.current().heroine() {
+            Some(heroine) => {
+                let stats = heroine.effective_stats();
+                lines.push(format!("HP: {}/{}", stats.hp, stats.max_hp));
+                lines.push(format!("Attack: {} ({} base)", stats.attack, heroine.stats.attack));
+                lines.push(format!("Defense: {} ({} base)", stats.defense, heroine.stats.defense));
+                lines.push(format!("Speed: {} ({} base)", stats.speed, heroine.stats.speed));
+                lines.push(String::new());
+                for &slot in SLOTS.iter() {
+                    let worn = heroine.inventory.equipped_in(slot);
+                    for i in range(0, slot.capacity()) {
+                        let name = match worn.as_slice().get(i) {
+                            Some(kind) => kind.name(),
+                            None => "-"
+                        };
+                        lines.push(format!("{}: {}", slot.name(), name));
+                    }
+                }
+            },
+            None => lines.push("HP: 0".to_string())
+        }
+
+        self.windows.stats.set_lines(lines.as_slice());
     }
 
     fn update_state(&mut self) {
@@ -224,25 +621,27 @@ impl<'a> Game<'a> {
         };
         match last_keypress {
             Some(ks) => {
-                match ks.key {
-                    Printable('/') => {
+                let weapon = match ks.key {
+                    Printable(c) => ItemKind::from_key(c),
+                    _ => None
+                };
+                let is_carried = match (weapon, self.dungeon.current().heroine()) {
+                    (Some(kind), Some(heroine)) => heroine.inventory.contains(kind),
+                    _ => false
+                };
+                match (ks.key, weapon) {
+                    (_, Some(kind)) if is_carried => {
                         let mut is: Box<AttackInputGameState> = box GameState::new();
-                        is.weapon = "Heroic Sword".to_string();
+                        is.weapon = kind;
                         self.game_state = is as Box<GameState>;
                     },
-                    Printable('^') => {
-                        let mut is: Box<AttackInputGameState> = box GameState::new();
-                        is.weapon = "Boomerang".to_string();
+                    (Printable('i'), _) => {
+                        let is: Box<InventoryGameState> = box GameState::new();
                         self.game_state = is as Box<GameState>;
                     },
-                    Printable('*') => {
-                        let mut is: Box<AttackInputGameState> = box GameState::new();
-                        is.weapon = "Deadly Bomb".to_string();
-                        self.game_state = is as Box<GameState>;
-                    },
-                    Printable('%') => {
-                        let mut is: Box<AttackInputGameState> = box GameState::new();
-                        is.weapon = "Delicious Lettuce".to_string();
+                    (Printable('d'), _) => {
+                        let mut is: Box<InventoryGameState> = box GameState::new();
+                        is.action = Some(InventoryAction::Drop);
                         self.game_state = is as Box<GameState>;
                     },
                     _ => {
@@ -257,10 +656,96 @@ impl<'a> Game<'a> {
 
     pub fn wait_for_keypress(&mut self) -> KeyboardInput {
         let key_state = self.rendering_component.wait_for_keypress();
+        self.press(key_state);
+        key_state
+    }
+
+    // Hands a key to the next `update`, whether a player pressed it or a replay did.
+    pub fn press(&mut self, key_state: KeyboardInput) {
         {
             let mut move_info = self.move_info.borrow_mut();
             move_info.deref_mut().last_keypress = Some(key_state)
         };
-        key_state
+        match self.recording {
+            Some(ref mut recording) => recording.record(key_state),
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::{ Windows, GameState, AttackInputGameState };
+    use window::{
+        WindowComponent,
+        TcodStatsWindowComponent,
+        TcodInputWindowComponent,
+        TcodMessagesWindowComponent,
+        TcodMapWindowComponent,
+        TcodInventoryWindowComponent
+    };
+    use input::{ KeyboardInput, KeyCode };
+    use input::Key;
+    use input::Key::{ SpecialKey, Printable };
+    use dungeon::Dungeon;
+    use mapgen::Level;
+    use movement::MoveInfo;
+    use terrain::Terrain;
+    use util::{ Bound, Point };
+
+    pub fn windows() -> Windows<'static> {
+        let sw: Box<TcodStatsWindowComponent> = box WindowComponent::new(Bound::new(79, 0, 99, 49));
+        let iw: Box<TcodInputWindowComponent> = box WindowComponent::new(Bound::new(0, 50, 99, 52));
+        let mw: Box<TcodMessagesWindowComponent> = box WindowComponent::new(Bound::new(0, 53, 99, 61));
+        let maw: Box<TcodMapWindowComponent> = box WindowComponent::new(Bound::new(0, 0, 78, 49));
+        let inw: Box<TcodInventoryWindowComponent> = box WindowComponent::new(Bound::new(20, 10, 58, 24));
+        Windows { input: iw, messages: mw, map: maw, stats: sw, inventory: inw }
+    }
+
+    // Opens the sword's aim prompt the way its hotkey does, then presses `key`.
+    fn press_after_aiming(key: Key) -> (AttackInputGameState, Windows<'static>) {
+        let bounds = Bound::new(0, 0, 19, 9);
+        let move_info = Rc::new(RefCell::new(MoveInfo::new(bounds, Point::new(2, 2))));
+        let level = Level {
+            terrain: Terrain::new(bounds),
+            start: Point::new(2, 2),
+            exit: Point::new(17, 7),
+            spawns: vec![],
+            items: vec![]
+        };
+        let mut dungeon = Dungeon::new(move_info.clone(), level, 1);
+        let mut windows = windows();
+        let mut state: AttackInputGameState = GameState::new();
+        state.enter(&mut windows);
+
+        for &key in [Printable('/'), key].iter() {
+            {
+                let mut info = move_info.borrow_mut();
+                info.deref_mut().last_keypress = Some(KeyboardInput { key: key });
+            }
+            state.update(&mut dungeon, &mut windows, move_info.clone());
+        }
+        (state, windows)
+    }
+
+    fn prompt_is_cleared(windows: &Windows) -> bool {
+        windows.input.get_messages().iter().all(|line| line.is_empty())
+    }
+
+    #[test]
+    fn escape_backs_out_of_the_aim_prompt() {
+        let (state, windows) = press_after_aiming(SpecialKey(KeyCode::Escape));
+        assert!(state.should_update_state());
+        assert!(prompt_is_cleared(&windows));
+    }
+
+    #[test]
+    fn any_other_key_backs_out_of_the_aim_prompt() {
+        let (state, windows) = press_after_aiming(Printable('x'));
+        assert!(state.should_update_state());
+        assert!(prompt_is_cleared(&windows));
     }
 }
@@ -1,16 +1,21 @@
+#[cfg(feature = "tcod")]
 extern crate tcod;
+#[cfg(feature = "tcod")]
 use self::tcod::{ KeyState };
 use self::Key::{ Printable, SpecialKey };
 
+#[deriving(Encodable, Decodable)]
 pub enum Key {
     Printable(char),
     SpecialKey(KeyCode)
 }
 
+#[deriving(Encodable, Decodable)]
 pub struct KeyboardInput {
     pub key: Key
 }
 
+#[deriving(Encodable, Decodable)]
 pub enum KeyCode {
     // Arrow Keys
     Up,
@@ -31,8 +36,10 @@ pub trait InputComponent<T> {
     fn translate_input(&self, T) -> KeyboardInput;
 }
 
+#[cfg(feature = "tcod")]
 pub struct TcodInputComponent;
 
+#[cfg(feature = "tcod")]
 impl InputComponent<KeyState> for TcodInputComponent {
     fn new() -> TcodInputComponent { TcodInputComponent }
      fn translate_input(&self, key_state: KeyState) -> KeyboardInput {
@@ -41,11 +48,15 @@ impl InputComponent<KeyState> for TcodInputComponent {
                 self::tcod::Key::Special(tcod::KeyCode::Number5) => Printable('%'),
                 self::tcod::Key::Special(tcod::KeyCode::Number6) => Printable('^'),
                 self::tcod::Key::Special(tcod::KeyCode::Number8) => Printable('*'),
+                self::tcod::Key::Printable('.') => Printable('>'),
+                self::tcod::Key::Printable(',') => Printable('<'),
+                self::tcod::Key::Printable('>') => Printable('>'),
+                self::tcod::Key::Printable('<') => Printable('<'),
                 _ => SpecialKey(KeyCode::None)
             }
          } else {
              match key_state.key {
-                self::tcod::Key::Printable('/') => Printable('/'),
+                self::tcod::Key::Printable(c) => Printable(c),
                 self::tcod::Key::Special(tcod::KeyCode::Up) => SpecialKey(KeyCode::Up),
                 self::tcod::Key::Special(tcod::KeyCode::Down) => SpecialKey(KeyCode::Down),
                 self::tcod::Key::Special(tcod::KeyCode::Left) => SpecialKey(KeyCode::Left),
@@ -60,3 +71,70 @@ impl InputComponent<KeyState> for TcodInputComponent {
          KeyboardInput { key: key }
      }
 }
+
+// Turns the bytes a terminal in raw mode sends for one keypress into a key. Shifted
+// characters arrive as themselves, so there is no Shift to deal with.
+pub struct TerminalInputComponent;
+
+impl InputComponent<Vec<u8>> for TerminalInputComponent {
+    fn new() -> TerminalInputComponent { TerminalInputComponent }
+
+    fn translate_input(&self, bytes: Vec<u8>) -> KeyboardInput {
+        let key = match bytes.as_slice() {
+            // Escape on its own, or Ctrl-C since raw mode stops it from interrupting.
+            [27] | [3] => SpecialKey(KeyCode::Escape),
+            // Arrow keys, in both the normal and the application cursor modes.
+            [27, b'[', b'A'] | [27, b'O', b'A'] => SpecialKey(KeyCode::Up),
+            [27, b'[', b'B'] | [27, b'O', b'B'] => SpecialKey(KeyCode::Down),
+            [27, b'[', b'C'] | [27, b'O', b'C'] => SpecialKey(KeyCode::Right),
+            [27, b'[', b'D'] | [27, b'O', b'D'] => SpecialKey(KeyCode::Left),
+            [c] if c >= b' ' && c <= b'~' => Printable(c as char),
+            _ => SpecialKey(KeyCode::None)
+        };
+
+        KeyboardInput { key: key }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ InputComponent, TerminalInputComponent, KeyCode };
+    use super::Key::{ Printable, SpecialKey };
+
+    fn translate(bytes: &[u8]) -> super::Key {
+        let input: TerminalInputComponent = InputComponent::new();
+        input.translate_input(bytes.to_vec()).key
+    }
+
+    #[test]
+    fn arrow_key_sequences_are_arrows() {
+        match (translate(&[27, b'[', b'A']), translate(&[27, b'O', b'D'])) {
+            (SpecialKey(KeyCode::Up), SpecialKey(KeyCode::Left)) => {},
+            _ => panic!("arrow keys weren't recognised")
+        }
+    }
+
+    #[test]
+    fn a_lone_escape_is_escape() {
+        match translate(&[27]) {
+            SpecialKey(KeyCode::Escape) => {},
+            _ => panic!("escape wasn't recognised")
+        }
+    }
+
+    #[test]
+    fn printable_bytes_are_characters() {
+        match (translate(b"g"), translate(b">")) {
+            (Printable('g'), Printable('>')) => {},
+            _ => panic!("characters weren't passed through")
+        }
+    }
+
+    #[test]
+    fn unknown_sequences_are_ignored() {
+        match translate(&[27, b'[', b'5', b'~']) {
+            SpecialKey(KeyCode::None) => {},
+            _ => panic!("an unknown sequence became a key")
+        }
+    }
+}
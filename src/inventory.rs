@@ -0,0 +1,130 @@
+use item::{ ItemKind, Slot };
+use stats::Bonus;
+
+pub static DEFAULT_CAPACITY: uint = 10;
+
+#[deriving(Clone, Encodable, Decodable)]
+pub struct Carried {
+    pub kind: ItemKind,
+    pub equipped: bool
+}
+
+// Equipped gear stays in the pack, flagged, so it still counts against the capacity
+// and leaves the pack like anything else when dropped or thrown.
+#[deriving(Clone, Encodable, Decodable)]
+pub struct Inventory {
+    pub items: Vec<Carried>,
+    pub capacity: uint
+}
+
+impl Inventory {
+    pub fn new(capacity: uint) -> Inventory {
+        Inventory { items: vec![], capacity: capacity }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.items.len() >= self.capacity
+    }
+
+    pub fn len(&self) -> uint {
+        self.items.len()
+    }
+
+    pub fn get(&self, index: uint) -> Option<ItemKind> {
+        self.items.as_slice().get(index).map(|carried| carried.kind)
+    }
+
+    pub fn is_equipped(&self, index: uint) -> bool {
+        match self.items.as_slice().get(index) {
+            Some(carried) => carried.equipped,
+            None => false
+        }
+    }
+
+    pub fn contains(&self, kind: ItemKind) -> bool {
+        self.items.iter().any(|carried| carried.kind == kind)
+    }
+
+    // Hands the item back when there is no room for it.
+    pub fn add(&mut self, kind: ItemKind) -> Result<(), ItemKind> {
+        if self.is_full() {
+            return Err(kind);
+        }
+        self.items.push(Carried { kind: kind, equipped: false });
+        Ok(())
+    }
+
+    pub fn remove(&mut self, index: uint) -> Option<ItemKind> {
+        if index >= self.items.len() {
+            return None;
+        }
+        self.items.remove(index).map(|carried| carried.kind)
+    }
+
+    pub fn remove_kind(&mut self, kind: ItemKind) -> Option<ItemKind> {
+        // Prefer giving up a spare over something that is being worn.
+        let spare = self.items.iter().position(|carried| carried.kind == kind && !carried.equipped);
+        let index = match spare {
+            Some(index) => Some(index),
+            None => self.items.iter().position(|carried| carried.kind == kind)
+        };
+        match index {
+            Some(index) => self.remove(index),
+            None => None
+        }
+    }
+
+    // Equips the item at `index`, taking off whatever has to make room for it in
+    // its slot. Returns what was taken off, or None if the item can't be equipped.
+    pub fn equip(&mut self, index: uint) -> Option<Vec<ItemKind>> {
+        let slot = match self.get(index).and_then(|kind| kind.def().slot) {
+            Some(slot) => slot,
+            None => return None
+        };
+
+        let mut worn: Vec<uint> = self.items.iter().enumerate()
+            .filter(|&(other, carried)| other != index && carried.equipped && carried.kind.def().slot == Some(slot))
+            .map(|(other, _)| other)
+            .collect();
+        let mut removed: Vec<ItemKind> = vec![];
+        while worn.len() >= slot.capacity() {
+            let other = worn.remove(0).unwrap();
+            self.items[other].equipped = false;
+            removed.push(self.items[other].kind);
+        }
+
+        self.items[index].equipped = true;
+        Some(removed)
+    }
+
+    pub fn unequip(&mut self, index: uint) {
+        if index < self.items.len() {
+            self.items[index].equipped = false;
+        }
+    }
+
+    pub fn equipped(&self) -> Vec<ItemKind> {
+        self.items.iter().filter(|carried| carried.equipped).map(|carried| carried.kind).collect()
+    }
+
+    pub fn equipped_in(&self, slot: Slot) -> Vec<ItemKind> {
+        self.equipped().into_iter().filter(|kind| kind.def().slot == Some(slot)).collect()
+    }
+
+    pub fn wielded(&self) -> Option<ItemKind> {
+        self.equipped_in(Slot::Weapon).into_iter().next()
+    }
+
+    pub fn bonus(&self) -> Bonus {
+        self.equipped().iter().fold(Bonus::none(), |total, kind| total.add(&kind.def().bonus))
+    }
+
+    // One line per carried item, lettered the way the inventory screen selects them.
+    pub fn describe(&self) -> Vec<String> {
+        self.items.iter().enumerate().map(|(index, carried)| {
+            let letter = (b'a' + index as u8) as char;
+            let equipped = if carried.equipped { " (equipped)" } else { "" };
+            format!("{}) {} {}{}", letter, carried.kind.def().display_char, carried.kind.name(), equipped)
+        }).collect()
+    }
+}
@@ -0,0 +1,380 @@
+use std::rand::Rng;
+
+use util::Point;
+use game::Windows;
+use maps::{ Maps, Layer, ActorId };
+use combat;
+use combat::{ Weapon, AttackResult };
+use fov::Fov;
+use stats::Bonus;
+
+#[deriving(Clone, PartialEq, Encodable, Decodable)]
+pub enum ItemKind {
+    HeroicSword,
+    Boomerang,
+    DeadlyBomb,
+    DeliciousLettuce,
+    LeatherArmour,
+    ChainMail,
+    WoodenShield,
+    RingOfStrength,
+    RingOfProtection,
+    RingOfSwiftness
+}
+
+#[deriving(PartialEq)]
+pub enum Slot {
+    Weapon,
+    Armour,
+    Shield,
+    Ring
+}
+
+impl Slot {
+    // How many items can be equipped in the slot at once.
+    pub fn capacity(&self) -> uint {
+        match *self {
+            Slot::Ring => 2,
+            _ => 1
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Slot::Weapon => "Weapon",
+            Slot::Armour => "Armour",
+            Slot::Shield => "Shield",
+            Slot::Ring => "Ring"
+        }
+    }
+}
+
+pub static SLOTS: [Slot, ..4] = [Slot::Weapon, Slot::Armour, Slot::Shield, Slot::Ring];
+
+pub enum Behaviour {
+    // Hits whatever stands in the adjacent cell.
+    Melee,
+    // Flies up to `range` cells, hits the first hostile in its path and comes back.
+    Returning(i32),
+    // Lands up to `range` cells away and hurts everything within `radius`, friends included.
+    Explosive(i32, i32),
+    // Lands up to `range` cells away and feeds the first friend it reaches for `heal` hit points.
+    Treat(i32, i32),
+    // Does nothing when used; it only helps while equipped.
+    Worn
+}
+
+pub struct ItemDef {
+    pub name: &'static str,
+    pub display_char: char,
+    pub verb: &'static str,
+    pub accuracy: i32,
+    pub min_damage: i32,
+    pub max_damage: i32,
+    pub behaviour: Behaviour,
+    pub slot: Option<Slot>,
+    pub bonus: Bonus
+}
+
+// How far anything goes when it is simply thrown rather than used.
+static THROW_RANGE: i32 = 6;
+
+impl ItemKind {
+    pub fn def(&self) -> ItemDef {
+        match *self {
+            ItemKind::HeroicSword => ItemDef {
+                name: "Heroic Sword",
+                display_char: '/',
+                verb: "swing",
+                accuracy: 80,
+                min_damage: 3,
+                max_damage: 8,
+                behaviour: Behaviour::Melee,
+                slot: Some(Slot::Weapon),
+                bonus: Bonus::none()
+            },
+            ItemKind::Boomerang => ItemDef {
+                name: "Boomerang",
+                display_char: '^',
+                verb: "throw",
+                accuracy: 70,
+                min_damage: 2,
+                max_damage: 5,
+                behaviour: Behaviour::Returning(6),
+                slot: None,
+                bonus: Bonus::none()
+            },
+            ItemKind::DeadlyBomb => ItemDef {
+                name: "Deadly Bomb",
+                display_char: '*',
+                verb: "lob",
+                accuracy: 100,
+                min_damage: 6,
+                max_damage: 12,
+                behaviour: Behaviour::Explosive(5, 2),
+                slot: None,
+                bonus: Bonus::none()
+            },
+            ItemKind::DeliciousLettuce => ItemDef {
+                name: "Delicious Lettuce",
+                display_char: '%',
+                verb: "toss",
+                accuracy: 100,
+                min_damage: 0,
+                max_damage: 0,
+                behaviour: Behaviour::Treat(5, 5),
+                slot: None,
+                bonus: Bonus::none()
+            },
+            ItemKind::LeatherArmour => ItemDef::gear("Leather Armour", '[', Slot::Armour, Bonus::new(0, 1, 0)),
+            // Heavy enough to slow its wearer down.
+            ItemKind::ChainMail => ItemDef::gear("Chain Mail", '[', Slot::Armour, Bonus::new(0, 3, -10)),
+            ItemKind::WoodenShield => ItemDef::gear("Wooden Shield", ']', Slot::Shield, Bonus::new(0, 1, 0)),
+            ItemKind::RingOfStrength => ItemDef::gear("Ring of Strength", '=', Slot::Ring, Bonus::new(2, 0, 0)),
+            ItemKind::RingOfProtection => ItemDef::gear("Ring of Protection", '=', Slot::Ring, Bonus::new(0, 1, 0)),
+            ItemKind::RingOfSwiftness => ItemDef::gear("Ring of Swiftness", '=', Slot::Ring, Bonus::new(0, 0, 20))
+        }
+    }
+
+    pub fn from_key(key: char) -> Option<ItemKind> {
+        match key {
+            '/' => Some(ItemKind::HeroicSword),
+            '^' => Some(ItemKind::Boomerang),
+            '*' => Some(ItemKind::DeadlyBomb),
+            '%' => Some(ItemKind::DeliciousLettuce),
+            _ => None
+        }
+    }
+
+    // Scattered loot leans towards the cheap stuff.
+    pub fn random<R: Rng>(rng: &mut R) -> ItemKind {
+        match rng.gen_range(0u, 20) {
+            0 => ItemKind::HeroicSword,
+            1 | 2 => ItemKind::Boomerang,
+            3 | 4 | 5 | 6 => ItemKind::DeadlyBomb,
+            7 | 8 | 9 | 10 | 11 => ItemKind::DeliciousLettuce,
+            12 | 13 => ItemKind::LeatherArmour,
+            14 => ItemKind::ChainMail,
+            15 | 16 => ItemKind::WoodenShield,
+            17 => ItemKind::RingOfStrength,
+            18 => ItemKind::RingOfProtection,
+            _ => ItemKind::RingOfSwiftness
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.def().name
+    }
+
+    pub fn weapon(&self) -> Weapon {
+        let def = self.def();
+        Weapon::new(def.name, def.accuracy, def.min_damage, def.max_damage)
+    }
+
+    pub fn is_equippable(&self) -> bool {
+        self.def().slot.is_some()
+    }
+
+    // Whether using the item needs a direction to aim it in.
+    pub fn is_aimed(&self) -> bool {
+        match self.def().behaviour {
+            Behaviour::Worn => false,
+            _ => true
+        }
+    }
+
+    pub fn prompt(&self) -> String {
+        let def = self.def();
+        format!("Which direction would you like to {} your {}? [Press an arrow key]", def.verb, def.name)
+    }
+
+    pub fn throw_prompt(&self) -> String {
+        format!("Which direction would you like to throw your {}? [Press an arrow key]", self.name())
+    }
+
+    // Returns whether using the item took the heroine's turn.
+    pub fn use_in_direction(&self, maps: &mut Maps, direction: Point, windows: &mut Windows) -> bool {
+        let (heroine, origin) = match maps.heroine_id() {
+            Some(id) => (id, maps.pcs.get(id).unwrap().position),
+            None => return false
+        };
+
+        match self.def().behaviour {
+            Behaviour::Worn => {
+                let msg = format!("You can't use your {} like that.", self.name());
+                windows.messages.buffer_message(msg.as_slice());
+                false
+            },
+            Behaviour::Melee => {
+                match hostile_at(maps, origin.offset(direction)) {
+                    Some(target) => {
+                        maps.attack((Layer::Pcs, heroine), target, &self.weapon(), windows);
+                        true
+                    },
+                    None => {
+                        windows.messages.buffer_message("No enemy in that direction!");
+                        false
+                    }
+                }
+            },
+            Behaviour::Returning(range) => {
+                let (_, hit) = fly(maps, origin, direction, range);
+                match hit {
+                    Some((layer, id)) if Layer::Pcs.is_hostile_to(layer) => {
+                        maps.attack((Layer::Pcs, heroine), (layer, id), &self.weapon(), windows);
+                    },
+                    Some((layer, id)) => {
+                        let msg = format!("Your {} bounces off the {}.", self.name(), maps.layer(layer).get(id).unwrap().name);
+                        windows.messages.buffer_message(msg.as_slice());
+                    },
+                    None => {}
+                }
+                let msg = format!("Your {} flies back to your hand.", self.name());
+                windows.messages.buffer_message(msg.as_slice());
+                true
+            },
+            Behaviour::Explosive(range, radius) => {
+                take_from_heroine(maps, heroine, *self);
+                let (landing, _) = fly(maps, origin, direction, range);
+                self.explode(maps, (Layer::Pcs, heroine), landing, radius, windows);
+                true
+            },
+            Behaviour::Treat(range, heal) => {
+                take_from_heroine(maps, heroine, *self);
+                let (landing, hit) = fly(maps, origin, direction, range);
+                match hit {
+                    Some((Layer::Friends, id)) => {
+                        let friend = maps.friends.get_mut(id).unwrap();
+                        friend.stats.heal(heal);
+                        let msg = format!("The {} happily gobbles up your {}!", friend.name, self.name());
+                        windows.messages.buffer_message(msg.as_slice());
+                    },
+                    _ => {
+                        maps.items.push(landing, *self);
+                        let msg = format!("Your {} lands on the floor.", self.name());
+                        windows.messages.buffer_message(msg.as_slice());
+                    }
+                }
+                true
+            }
+        }
+    }
+
+    // Throws the item itself, whatever it is for. It hurts the first hostile in its
+    // path and then stays on the floor where it came down.
+    pub fn throw_in_direction(&self, maps: &mut Maps, direction: Point, windows: &mut Windows) -> bool {
+        let (heroine, origin) = match maps.heroine_id() {
+            Some(id) => (id, maps.pcs.get(id).unwrap().position),
+            None => return false
+        };
+        if take_from_heroine(maps, heroine, *self).is_none() {
+            return false;
+        }
+
+        let (landing, hit) = fly(maps, origin, direction, THROW_RANGE);
+        match hit {
+            Some((layer, id)) if Layer::Pcs.is_hostile_to(layer) => {
+                maps.attack((Layer::Pcs, heroine), (layer, id), &self.weapon(), windows);
+            },
+            _ => {}
+        }
+        maps.items.push(landing, *self);
+        let msg = format!("Your {} lands on the floor.", self.name());
+        windows.messages.buffer_message(msg.as_slice());
+        true
+    }
+
+    fn explode(&self, maps: &mut Maps, attacker: (Layer, ActorId), center: Point, radius: i32, windows: &mut Windows) {
+        let msg = format!("Your {} explodes!", self.name());
+        windows.messages.buffer_message(msg.as_slice());
+
+        let (attacker_layer, attacker_id) = attacker;
+        let attacker_stats = match maps.layer(attacker_layer).get(attacker_id) {
+            Some(actor) => actor.effective_stats(),
+            None => return
+        };
+        let blast = Fov::compute(&*maps.terrain, center, radius);
+        let weapon = self.weapon();
+
+        let mut caught: Vec<(Layer, ActorId)> = vec![];
+        for &layer in [Layer::Pcs, Layer::Friends, Layer::Enemies].iter() {
+            for id in maps.layer(layer).ids().into_iter() {
+                if blast.is_visible(maps.layer(layer).get(id).unwrap().position) {
+                    caught.push((layer, id));
+                }
+            }
+        }
+
+        for &(layer, id) in caught.iter() {
+            let mut defender_stats = maps.layer(layer).get(id).unwrap().effective_stats();
+            let result = maps.roll(|rng| combat::roll_damage(rng, &attacker_stats, &weapon, &mut defender_stats));
+            let name = {
+                let actor = maps.layer_mut(layer).get_mut(id).unwrap();
+                actor.stats.hp = defender_stats.hp;
+                actor.name.clone()
+            };
+            let msg = match (layer, result) {
+                (Layer::Pcs, AttackResult::Hit(damage)) | (Layer::Pcs, AttackResult::Kill(damage)) =>
+                    format!("The blast hits you for {} damage!", damage),
+                (_, AttackResult::Hit(damage)) | (_, AttackResult::Kill(damage)) =>
+                    format!("The blast hits the {} for {} damage!", name, damage),
+                (_, AttackResult::Miss) => continue
+            };
+            windows.messages.buffer_message(msg.as_slice());
+
+            match result {
+                AttackResult::Kill(_) => maps.kill(layer, id, windows),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl ItemDef {
+    // Armour, shields and rings all make poor missiles.
+    fn gear(name: &'static str, display_char: char, slot: Slot, bonus: Bonus) -> ItemDef {
+        ItemDef {
+            name: name,
+            display_char: display_char,
+            verb: "throw",
+            accuracy: 60,
+            min_damage: 1,
+            max_damage: 2,
+            behaviour: Behaviour::Worn,
+            slot: Some(slot),
+            bonus: bonus
+        }
+    }
+}
+
+fn take_from_heroine(maps: &mut Maps, heroine: ActorId, kind: ItemKind) -> Option<ItemKind> {
+    match maps.pcs.get_mut(heroine) {
+        Some(actor) => actor.inventory.remove_kind(kind),
+        None => None
+    }
+}
+
+fn hostile_at(maps: &Maps, point: Point) -> Option<(Layer, ActorId)> {
+    match maps.occupant_at(point) {
+        Some((layer, id)) if Layer::Pcs.is_hostile_to(layer) => Some((layer, id)),
+        _ => None
+    }
+}
+
+// Moves a thrown item from `origin` until it runs out of range, would enter a
+// blocking tile, or reaches an actor. Returns where it stopped and who it reached.
+fn fly(maps: &Maps, origin: Point, direction: Point, max_distance: i32) -> (Point, Option<(Layer, ActorId)>) {
+    let mut position = origin;
+    for _ in range(0, max_distance) {
+        let next = position.offset(direction);
+        if !maps.terrain.is_passable(next) {
+            break;
+        }
+        position = next;
+        match maps.occupant_at(position) {
+            Some(occupant) => return (position, Some(occupant)),
+            None => {}
+        }
+    }
+    (position, None)
+}
@@ -2,7 +2,6 @@
 
 macro_rules! window_component_getters(
     () => {
-        fn get_console(&mut self) -> &mut Console { &mut self.console }
         fn get_bounds(&self) -> Bound { self.bounds }
         fn get_bg_color(&self) -> Color { self.background_color }
         fn get_mut_messages(&mut self) -> &mut Vec<Box<String>> { &mut self.messages }
@@ -14,7 +13,6 @@ macro_rules! window_component_getters(
 macro_rules! window_component_def(
     ($name:ident) => {
         pub struct $name {
-            pub console: Console,
             pub background_color: Color,
             bounds: Bound,
             messages: Vec<Box<String>>,
@@ -26,12 +24,7 @@ macro_rules! window_component_def(
 macro_rules! window_component_init(
     ($name:ident, $color:expr, $max_messages:expr) => {
         fn new(bounds: Bound) -> $name {
-            let height = bounds.max.y - bounds.min.y + 1;
-            let width = bounds.max.x - bounds.min.x + 1;
-            let console = Console::new(width as int, height as int);
-
             $name {
-                console: console,
                 background_color: $color,
                 bounds: bounds,
                 messages: vec![],
@@ -41,7 +34,10 @@ macro_rules! window_component_init(
     }
 )
 
+extern crate serialize;
+
 pub mod util;
+pub mod color;
 pub mod game;
 pub mod actor;
 pub mod rendering;
@@ -49,3 +45,19 @@ pub mod window;
 pub mod movement;
 pub mod input;
 pub mod maps;
+pub mod terrain;
+pub mod mapgen;
+pub mod fov;
+pub mod dungeon;
+pub mod camera;
+pub mod stats;
+pub mod combat;
+pub mod item;
+pub mod inventory;
+pub mod scheduler;
+pub mod action;
+pub mod pathfinding;
+pub mod dijkstra;
+pub mod awareness;
+pub mod save;
+pub mod replay;
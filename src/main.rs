@@ -1,44 +1,142 @@
+#[cfg(feature = "tcod")]
 extern crate tcod;
 extern crate dwemthys;
 
+#[cfg(feature = "tcod")]
 use tcod::Console;
-use dwemthys::game::Game;
-use dwemthys::actor::Actor;
+use dwemthys::game::{ Game, SCREEN_BOUNDS };
+use dwemthys::rendering::{ RenderingComponent, TerminalRenderingComponent };
+#[cfg(feature = "tcod")]
+use dwemthys::rendering::TcodRenderingComponent;
 use dwemthys::input::KeyCode;
 use dwemthys::input::Key::SpecialKey;
-use dwemthys::util::Point;
+use dwemthys::replay::{ Replay, REPLAY_PATH };
+use dwemthys::save::Store;
+
+// Command line options:
+//   --seed N         play the dungeon of an earlier game again
+//   --replay FILE    play back a recorded game as fast as possible, then carry on by hand
+//   --step           with --replay, wait for a key before each recorded one; Escape
+//                    fast-forwards through the rest
+//   --terminal       draw in the terminal instead of a libtcod window, e.g. over SSH.
+//                    Builds without the `tcod` feature always do.
+fn arg_value(name: &str) -> Option<String> {
+    let args = std::os::args();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg.as_slice() == name {
+            return args.next().map(|value| value.clone());
+        }
+    }
+    None
+}
+
+fn has_flag(name: &str) -> bool {
+    std::os::args().iter().any(|arg| arg.as_slice() == name)
+}
+
+#[cfg(feature = "tcod")]
+fn rendering_component(use_terminal: bool) -> Box<RenderingComponent + 'static> {
+    if use_terminal {
+        let rc: Box<TerminalRenderingComponent> = box RenderingComponent::new(SCREEN_BOUNDS);
+        rc as Box<RenderingComponent>
+    } else {
+        let rc: Box<TcodRenderingComponent> = box RenderingComponent::new(SCREEN_BOUNDS);
+        rc as Box<RenderingComponent>
+    }
+}
+
+#[cfg(not(feature = "tcod"))]
+fn rendering_component(_: bool) -> Box<RenderingComponent + 'static> {
+    let rc: Box<TerminalRenderingComponent> = box RenderingComponent::new(SCREEN_BOUNDS);
+    rc as Box<RenderingComponent>
+}
+
+#[cfg(feature = "tcod")]
+fn window_closed(use_terminal: bool) -> bool {
+    !use_terminal && Console::window_closed()
+}
+
+#[cfg(not(feature = "tcod"))]
+fn window_closed(_: bool) -> bool {
+    false
+}
+
+fn complain(msg: String) {
+    std::io::stderr().write_line(msg.as_slice()).ok();
+}
 
 fn main() {
-    let mut game = Game::new();
-    let char_location = { game.move_info.borrow().deref().char_location };
-    game.maps.friends.push_actor(
-        Point::new(10, 10),
-        box Actor::dog(10, 10, game.move_info.clone())
-    );
-    game.maps.friends.push_actor(
-        Point::new(40, 25),
-        box Actor::cat(40, 25, game.move_info.clone())
-    );
-    game.maps.enemies.push_actor(
-        Point::new(20, 20),
-        box Actor::kobold(20, 20, game.move_info.clone())
-    );
-    game.maps.pcs.push_actor(
-        char_location,
-        box Actor::heroine(game.move_info.clone())
-    );
+    let replay = match arg_value("--replay") {
+        Some(path) => match Replay::read(&Path::new(path.as_slice())) {
+            Ok(replay) => Some(replay),
+            Err(e) => return complain(format!("Couldn't read the replay {}: {}", path, e))
+        },
+        None => None
+    };
+    let is_replaying = replay.is_some();
 
-    game.render();
+    let use_terminal = has_flag("--terminal");
+    let rc = rendering_component(use_terminal);
+    let mut game = match replay {
+        Some(ref replay) => Game::with_seed(rc, replay.seed, Store::default()),
+        None => Game::new(rc, arg_value("--seed").and_then(|seed| from_str(seed.as_slice())), Store::default())
+    };
+    // Watching a replay records nothing, so it can't overwrite the last game's recording,
+    // which may well be the one being watched.
+    if is_replaying {
+        game.recording = None;
+    }
 
-    while !(Console::window_closed() || game.exit) {
-        let keypress = game.wait_for_keypress();
+    game.render();
 
-        match keypress.key {
-            SpecialKey(KeyCode::Escape) => game.exit = true,
-            _ => {}
-        }
+    match replay {
+        Some(replay) => {
+            let mut stepping = has_flag("--step");
+            for &input in replay.inputs.iter() {
+                if window_closed(use_terminal) || game.exit {
+                    break;
+                }
+                if stepping {
+                    match game.rendering_component.wait_for_keypress().key {
+                        SpecialKey(KeyCode::Escape) => stepping = false,
+                        _ => {}
+                    }
+                }
+                game.press(input);
+                game.update();
+                game.render();
+            }
+        },
+        None => {}
+    }
 
+    while !(window_closed(use_terminal) || game.exit) {
+        game.wait_for_keypress();
         game.update();
         game.render();
     }
+
+    let mut problems = vec![];
+    match game.recording {
+        Some(ref recording) => match recording.write(&Path::new(REPLAY_PATH)) {
+            Ok(()) => {},
+            Err(e) => problems.push(format!("Couldn't write the replay: {}", e))
+        },
+        None => {}
+    }
+
+    // A replayed game is someone else's; it mustn't take the place of the player's save.
+    if !is_replaying {
+        match game.save() {
+            Ok(()) => {},
+            Err(e) => problems.push(format!("Couldn't save the game: {}", e))
+        }
+    }
+
+    // Only complain once the terminal backend has handed the terminal back.
+    drop(game);
+    for problem in problems.into_iter() {
+        complain(problem);
+    }
 }
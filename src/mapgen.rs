@@ -0,0 +1,470 @@
+use std::cmp::{ max, min };
+use std::rand::{ Rng, SeedableRng, XorShiftRng };
+
+use util::{ Bound, Point };
+use terrain::{ Terrain, Tile };
+use item::ItemKind;
+
+// Roughly one room attempt per 128 cells keeps big levels as dense as small ones.
+static CELLS_PER_ROOM_ATTEMPT: uint = 128;
+static ROOM_MIN_SIZE: i32 = 4;
+static ROOM_MAX_SIZE: i32 = 12;
+static MAX_FRIENDS_PER_ROOM: uint = 1;
+static MAX_ENEMIES_PER_ROOM: uint = 2;
+static MAX_ITEMS_PER_ROOM: uint = 1;
+
+static KOBOLD_GEAR_CHANCE: uint = 4;
+// Jackals only ever turn up in packs.
+static JACKAL_PACK_CHANCE: uint = 6;
+static JACKAL_PACK_SIZE: uint = 3;
+
+static CAVE_WALL_PERCENT: uint = 45;
+static CAVE_SMOOTHING_STEPS: uint = 5;
+static CAVE_TILES_PER_FRIEND: uint = 250;
+static CAVE_TILES_PER_ENEMY: uint = 150;
+static CAVE_TILES_PER_ITEM: uint = 200;
+static CAVE_TILES_PER_PACK: uint = 800;
+
+#[deriving(PartialEq, Show)]
+pub enum LevelStyle {
+    Rooms,
+    Caves
+}
+
+impl LevelStyle {
+    // The dungeon's usual mix, for levels whose spec doesn't ask for anything else.
+    pub fn for_depth(depth: uint) -> LevelStyle {
+        if depth % 3 == 0 { LevelStyle::Caves } else { LevelStyle::Rooms }
+    }
+}
+
+// Everything the generator needs to build one level.
+pub struct LevelSpec {
+    pub style: LevelStyle,
+    pub bounds: Bound,
+    pub seed: u32
+}
+
+impl LevelSpec {
+    // Set `style` on the result to build something other than the usual level there.
+    pub fn for_depth(depth: uint, bounds: Bound, seed: u32) -> LevelSpec {
+        LevelSpec {
+            style: LevelStyle::for_depth(depth),
+            bounds: bounds,
+            // Every depth gets its own seed so levels don't depend on the order they're visited in.
+            seed: seed ^ (depth as u32 * 0x9e3779b9u32)
+        }
+    }
+}
+
+#[deriving(PartialEq)]
+pub enum Spawn {
+    Dog,
+    Cat,
+    // Some kobolds turn up wearing or wielding something the heroine might want.
+    Kobold(Option<ItemKind>),
+    Jackal
+}
+
+pub struct Level {
+    pub terrain: Terrain,
+    pub start: Point,
+    pub exit: Point,
+    pub spawns: Vec<(Point, Spawn)>,
+    pub items: Vec<(Point, ItemKind)>
+}
+
+pub fn rng_from_seed(seed: u32) -> XorShiftRng {
+    // XorShiftRng refuses an all zero seed, so mix the seed into fixed non-zero words.
+    SeedableRng::from_seed([0x193a6754u32, 0xa8a7d469u32 ^ seed, 0x97830e05u32, 0x113ba7bbu32])
+}
+
+fn kobold<R: Rng>(rng: &mut R) -> Spawn {
+    if rng.gen_range(0, KOBOLD_GEAR_CHANCE) != 0 {
+        return Spawn::Kobold(None);
+    }
+    let gear = match rng.gen_range(0u, 3) {
+        0 => ItemKind::LeatherArmour,
+        1 => ItemKind::WoodenShield,
+        _ => ItemKind::HeroicSword
+    };
+    Spawn::Kobold(Some(gear))
+}
+
+struct Room {
+    bounds: Bound
+}
+
+impl Room {
+    fn new(x: i32, y: i32, width: i32, height: i32) -> Room {
+        Room { bounds: Bound::new(x, y, x + width - 1, y + height - 1) }
+    }
+
+    fn center(&self) -> Point {
+        Point::new(
+            (self.bounds.min.x + self.bounds.max.x) / 2,
+            (self.bounds.min.y + self.bounds.max.y) / 2
+        )
+    }
+
+    fn intersects(&self, other: &Room) -> bool {
+        // Rooms need a wall between them, so touching counts as intersecting.
+        self.bounds.min.x <= other.bounds.max.x + 1 &&
+        self.bounds.max.x + 1 >= other.bounds.min.x &&
+        self.bounds.min.y <= other.bounds.max.y + 1 &&
+        self.bounds.max.y + 1 >= other.bounds.min.y
+    }
+
+    fn random_point<R: Rng>(&self, rng: &mut R) -> Point {
+        Point::new(
+            rng.gen_range(self.bounds.min.x, self.bounds.max.x + 1),
+            rng.gen_range(self.bounds.min.y, self.bounds.max.y + 1)
+        )
+    }
+}
+
+pub fn generate(spec: &LevelSpec) -> Level {
+    match spec.style {
+        LevelStyle::Rooms => generate_rooms(spec.bounds, spec.seed),
+        LevelStyle::Caves => generate_caves(spec.bounds, spec.seed)
+    }
+}
+
+pub fn generate_rooms(bounds: Bound, seed: u32) -> Level {
+    let mut rng = rng_from_seed(seed);
+    let mut terrain = Terrain::filled(bounds, Tile::Wall);
+    let mut rooms: Vec<Room> = vec![];
+    for _ in range(0, bounds.area() / CELLS_PER_ROOM_ATTEMPT) {
+        let width = rng.gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
+        let height = rng.gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
+        if width > bounds.width() - 2 || height > bounds.height() - 2 {
+            continue;
+        }
+        let x = rng.gen_range(bounds.min.x + 1, bounds.max.x - width + 1);
+        let y = rng.gen_range(bounds.min.y + 1, bounds.max.y - height + 1);
+        let room = Room::new(x, y, width, height);
+
+        if rooms.iter().any(|other| room.intersects(other)) {
+            continue;
+        }
+
+        carve_room(&mut terrain, &room);
+        match rooms.last() {
+            Some(previous) => {
+                let (from, to) = (previous.center(), room.center());
+                if rng.gen() {
+                    carve_h_corridor(&mut terrain, from.x, to.x, from.y);
+                    carve_v_corridor(&mut terrain, from.y, to.y, to.x);
+                } else {
+                    carve_v_corridor(&mut terrain, from.y, to.y, from.x);
+                    carve_h_corridor(&mut terrain, from.x, to.x, to.y);
+                }
+            },
+            None => {}
+        }
+        rooms.push(room);
+    }
+
+    if rooms.is_empty() {
+        // Only happens for tiny bounds, but the heroine still needs somewhere to stand.
+        // Keep a wall round the edge where there's room for one, and stay inside the bounds where there isn't.
+        let (x, y) = (min(bounds.min.x + 1, bounds.max.x), min(bounds.min.y + 1, bounds.max.y));
+        let room = Room::new(x, y,
+                             max(x, bounds.max.x - 1) - x + 1,
+                             max(y, bounds.max.y - 1) - y + 1);
+        carve_room(&mut terrain, &room);
+        rooms.push(room);
+    }
+
+    let start = rooms[0].center();
+    let exit = if rooms.len() > 1 {
+        rooms[rooms.len() - 1].center()
+    } else {
+        // The up stairs go at the start, so the way down has to be somewhere else in the room.
+        let mut exit = rooms[0].random_point(&mut rng);
+        while rooms[0].bounds.area() > 1 && exit.x == start.x && exit.y == start.y {
+            exit = rooms[0].random_point(&mut rng);
+        }
+        exit
+    };
+    let mut spawns: Vec<(Point, Spawn)> = vec![];
+    let mut items: Vec<(Point, ItemKind)> = vec![];
+    for room in rooms.iter().skip(1) {
+        for _ in range(0, rng.gen_range(0, MAX_ITEMS_PER_ROOM + 1)) {
+            let point = room.random_point(&mut rng);
+            items.push((point, ItemKind::random(&mut rng)));
+        }
+        for _ in range(0, rng.gen_range(0, MAX_FRIENDS_PER_ROOM + 1)) {
+            let point = room.random_point(&mut rng);
+            let spawn = if rng.gen() { Spawn::Dog } else { Spawn::Cat };
+            spawns.push((point, spawn));
+        }
+        for _ in range(0, rng.gen_range(0, MAX_ENEMIES_PER_ROOM + 1)) {
+            let point = room.random_point(&mut rng);
+            spawns.push((point, kobold(&mut rng)));
+        }
+        if rng.gen_range(0, JACKAL_PACK_CHANCE) == 0 {
+            for _ in range(0, JACKAL_PACK_SIZE) {
+                spawns.push((room.random_point(&mut rng), Spawn::Jackal));
+            }
+        }
+    }
+
+    Level { terrain: terrain, start: start, exit: exit, spawns: spawns, items: items }
+}
+
+fn carve_room(terrain: &mut Terrain, room: &Room) {
+    for point in room.bounds.cells() {
+        terrain.set(point, Tile::Floor);
+    }
+}
+
+fn carve_h_corridor(terrain: &mut Terrain, x1: i32, x2: i32, y: i32) {
+    for x in range(min(x1, x2), max(x1, x2) + 1) {
+        terrain.set(Point::new(x, y), Tile::Floor);
+    }
+}
+
+fn carve_v_corridor(terrain: &mut Terrain, y1: i32, y2: i32, x: i32) {
+    for y in range(min(y1, y2), max(y1, y2) + 1) {
+        terrain.set(Point::new(x, y), Tile::Floor);
+    }
+}
+
+pub fn generate_caves(bounds: Bound, seed: u32) -> Level {
+    let mut rng = rng_from_seed(seed);
+    let mut terrain = Terrain::filled(bounds, Tile::Wall);
+
+    for x in range(bounds.min.x + 1, bounds.max.x) {
+        for y in range(bounds.min.y + 1, bounds.max.y) {
+            if rng.gen_range(0u, 100) >= CAVE_WALL_PERCENT {
+                terrain.set(Point::new(x, y), Tile::Floor);
+            }
+        }
+    }
+
+    for _ in range(0, CAVE_SMOOTHING_STEPS) {
+        terrain = smooth_cave(&terrain);
+    }
+
+    // Wall off every pocket the heroine couldn't walk to.
+    let cave = largest_region(&terrain);
+    terrain = Terrain::filled(bounds, Tile::Wall);
+    for &point in cave.iter() {
+        terrain.set(point, Tile::Floor);
+    }
+
+    if cave.is_empty() {
+        // The automaton closed everything off; open a cell for the heroine and one beside it for the stairs.
+        let start = Point::new((bounds.min.x + bounds.max.x) / 2, (bounds.min.y + bounds.max.y) / 2);
+        let exit = if start.x + 1 < bounds.max.x { start.offset_x(1) } else { start };
+        terrain.set(start, Tile::Floor);
+        terrain.set(exit, Tile::Floor);
+        return Level { terrain: terrain, start: start, exit: exit, spawns: vec![], items: vec![] };
+    }
+
+    let start = cave[rng.gen_range(0, cave.len())];
+    let mut exit = cave[rng.gen_range(0, cave.len())];
+    while cave.len() > 1 && exit.x == start.x && exit.y == start.y {
+        exit = cave[rng.gen_range(0, cave.len())];
+    }
+    let mut spawns: Vec<(Point, Spawn)> = vec![];
+    for _ in range(0, cave.len() / CAVE_TILES_PER_FRIEND) {
+        let point = cave[rng.gen_range(0, cave.len())];
+        let spawn = if rng.gen() { Spawn::Dog } else { Spawn::Cat };
+        spawns.push((point, spawn));
+    }
+    for _ in range(0, cave.len() / CAVE_TILES_PER_ENEMY) {
+        let point = cave[rng.gen_range(0, cave.len())];
+        spawns.push((point, kobold(&mut rng)));
+    }
+    for _ in range(0, cave.len() / CAVE_TILES_PER_PACK) {
+        // Packmates that would land in a wall are simply left out.
+        let den = cave[rng.gen_range(0, cave.len())];
+        for i in range(0, JACKAL_PACK_SIZE as i32) {
+            spawns.push((Point::new(den.x + i - 1, den.y), Spawn::Jackal));
+        }
+    }
+    let mut items: Vec<(Point, ItemKind)> = vec![];
+    for _ in range(0, cave.len() / CAVE_TILES_PER_ITEM) {
+        let point = cave[rng.gen_range(0, cave.len())];
+        items.push((point, ItemKind::random(&mut rng)));
+    }
+
+    Level { terrain: terrain, start: start, exit: exit, spawns: spawns, items: items }
+}
+
+fn smooth_cave(terrain: &Terrain) -> Terrain {
+    let bounds = terrain.bounds();
+    let mut smoothed = Terrain::filled(bounds, Tile::Wall);
+
+    for x in range(bounds.min.x + 1, bounds.max.x) {
+        for y in range(bounds.min.y + 1, bounds.max.y) {
+            let mut walls = 0u;
+            for dx in range(-1i32, 2) {
+                for dy in range(-1i32, 2) {
+                    if !terrain.is_passable(Point::new(x + dx, y + dy)) {
+                        walls += 1;
+                    }
+                }
+            }
+            if walls < 5 {
+                smoothed.set(Point::new(x, y), Tile::Floor);
+            }
+        }
+    }
+
+    smoothed
+}
+
+fn largest_region(terrain: &Terrain) -> Vec<Point> {
+    let bounds = terrain.bounds();
+    let mut seen = Vec::from_elem(bounds.width() as uint, Vec::from_elem(bounds.height() as uint, false));
+    let mut largest: Vec<Point> = vec![];
+
+    for origin in bounds.cells() {
+        let (x, y) = bounds.grid_index(origin).unwrap();
+        if !terrain.is_passable(origin) || seen[x][y] {
+            continue;
+        }
+
+        let mut region: Vec<Point> = vec![];
+        let mut stack = vec![origin];
+        seen[x][y] = true;
+        while let Some(point) = stack.pop() {
+            region.push(point);
+            let neighbours = [point.offset_x(1), point.offset_x(-1), point.offset_y(1), point.offset_y(-1)];
+            for &next in neighbours.iter() {
+                if !terrain.is_passable(next) {
+                    continue;
+                }
+                let (nx, ny) = bounds.grid_index(next).unwrap();
+                if !seen[nx][ny] {
+                    seen[nx][ny] = true;
+                    stack.push(next);
+                }
+            }
+        }
+
+        if region.len() > largest.len() {
+            largest = region;
+        }
+    }
+
+    largest
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ Level, LevelSpec, LevelStyle, generate, generate_rooms, generate_caves, largest_region };
+    use util::{ Bound, Point };
+
+    static SEED: u32 = 1234;
+
+    fn bounds() -> Bound {
+        Bound::new(0, 0, 79, 49)
+    }
+
+    fn same_point(a: Point, b: Point) -> bool {
+        a.x == b.x && a.y == b.y
+    }
+
+    fn assert_same_level(a: &Level, b: &Level) {
+        let bounds = a.terrain.bounds();
+        for x in range(bounds.min.x, bounds.max.x + 1) {
+            for y in range(bounds.min.y, bounds.max.y + 1) {
+                assert!(a.terrain.get(Point::new(x, y)) == b.terrain.get(Point::new(x, y)));
+            }
+        }
+        assert!(same_point(a.start, b.start));
+        assert!(same_point(a.exit, b.exit));
+        assert_eq!(a.spawns.len(), b.spawns.len());
+        for (&(p, x), &(q, y)) in a.spawns.iter().zip(b.spawns.iter()) {
+            assert!(same_point(p, q));
+            assert!(x == y);
+        }
+    }
+
+    // The up stairs go on the start, so the way down mustn't.
+    fn assert_stairs_apart(level: &Level) {
+        assert!(level.terrain.is_passable(level.exit));
+        assert!(!same_point(level.start, level.exit));
+    }
+
+    #[test]
+    fn rooms_are_the_same_for_the_same_seed() {
+        assert_same_level(&generate_rooms(bounds(), SEED), &generate_rooms(bounds(), SEED));
+    }
+
+    #[test]
+    fn rooms_start_on_open_ground() {
+        for seed in range(0u32, 20) {
+            let level = generate_rooms(bounds(), seed);
+            assert!(level.terrain.is_passable(level.start));
+            assert_stairs_apart(&level);
+        }
+    }
+
+    #[test]
+    fn rooms_fit_inside_tiny_bounds() {
+        let tiny = [Bound::new(0, 0, 0, 0), Bound::new(3, 4, 4, 5), Bound::new(0, 0, 2, 2), Bound::new(0, 0, 6, 1)];
+        for &bounds in tiny.iter() {
+            let level = generate_rooms(bounds, SEED);
+            assert!(level.terrain.is_passable(level.start));
+            assert!(level.terrain.is_passable(level.exit));
+        }
+    }
+
+    #[test]
+    fn stairs_are_apart_even_in_a_single_room() {
+        let level = generate_rooms(Bound::new(0, 0, 6, 6), SEED);
+        assert_stairs_apart(&level);
+    }
+
+    #[test]
+    fn stairs_are_apart_even_in_a_closed_off_cave() {
+        // Too small for the automaton to leave any floor.
+        let level = generate_caves(Bound::new(0, 0, 4, 4), SEED);
+        assert_stairs_apart(&level);
+    }
+
+    #[test]
+    fn caves_are_the_same_for_the_same_seed() {
+        assert_same_level(&generate_caves(bounds(), SEED), &generate_caves(bounds(), SEED));
+    }
+
+    #[test]
+    fn caves_start_on_open_ground() {
+        for seed in range(0u32, 20) {
+            let level = generate_caves(bounds(), seed);
+            assert!(level.terrain.is_passable(level.start));
+            assert_stairs_apart(&level);
+        }
+    }
+
+    #[test]
+    fn the_spec_chooses_the_style() {
+        let mut spec = LevelSpec::for_depth(1, bounds(), SEED);
+        assert_eq!(spec.style, LevelStyle::Rooms);
+        spec.style = LevelStyle::Caves;
+        assert_same_level(&generate(&spec), &generate_caves(spec.bounds, spec.seed));
+    }
+
+    #[test]
+    fn every_cave_floor_can_be_walked_to() {
+        for seed in range(0u32, 20) {
+            let level = generate_caves(bounds(), seed);
+            let area = level.terrain.bounds();
+            let mut floors = 0u;
+            for x in range(area.min.x, area.max.x + 1) {
+                for y in range(area.min.y, area.max.y + 1) {
+                    if level.terrain.is_passable(Point::new(x, y)) {
+                        floors += 1;
+                    }
+                }
+            }
+            // One region holding every floor means there are no pockets left over.
+            assert_eq!(largest_region(&level.terrain).len(), floors);
+        }
+    }
+}
@@ -1,60 +1,650 @@
 use std::cell::RefCell;
+use std::cmp::max;
+use std::collections::BTreeMap;
 use std::rc::Rc;
 
 use util::{ Bound, Point };
+use color::Color;
 use rendering::RenderingComponent;
 use actor::Actor;
 use game::Windows;
-use movement::MoveInfo;
+use movement::{ MoveInfo, GameRng };
+use terrain::{ Terrain, Tile };
+use mapgen::{ Level, Spawn };
+use fov::{ Fov, Explored };
+use camera::Camera;
+use combat;
+use combat::{ Weapon, AttackResult };
+use item::ItemKind;
+use scheduler;
+use action::Action;
+use dijkstra::{ FlowMaps, TravelMap };
+
+static SIGHT_RADIUS: i32 = 10;
+// Stops auto-travel that somehow never arrives.
+static MAX_TRAVEL_STEPS: uint = 500;
+
+#[deriving(PartialEq)]
+pub enum Layer {
+    Pcs,
+    Friends,
+    Enemies
+}
+
+pub static LAYERS: [Layer, ..3] = [Layer::Pcs, Layer::Friends, Layer::Enemies];
+
+impl Layer {
+    pub fn is_hostile_to(&self, other: Layer) -> bool {
+        match (*self, other) {
+            (Layer::Enemies, Layer::Pcs) | (Layer::Enemies, Layer::Friends) => true,
+            (Layer::Pcs, Layer::Enemies) | (Layer::Friends, Layer::Enemies) => true,
+            _ => false
+        }
+    }
+}
+
+#[deriving(Encodable, Decodable)]
+pub struct Corpse {
+    pub position: Point,
+    pub name: String
+}
 
 pub struct Maps<'a> {
-    pub terrain: Box<Map<'a>>,
+    pub terrain: Box<Terrain>,
     pub enemies: Box<Map<'a>>,
     pub friends: Box<Map<'a>>,
-    pub pcs: Box<Map<'a>>
+    pub pcs: Box<Map<'a>>,
+    pub items: Box<ItemMap>,
+    pub corpses: Vec<Corpse>,
+    pub explored: Box<Explored>,
+    pub up_stairs: Option<Point>,
+    pub down_stairs: Point,
+    // Shared by the monsters that hunt or flee the heroine, see `refresh_flow_maps`.
+    pub flow: Option<FlowMaps>,
+    // The heroine's way to the stairs down, see `refresh_travel_map`.
+    pub travel: Option<TravelMap>,
+    // What the heroine could see when she last acted; monsters see her by the same rule.
+    pub heroine_fov: Option<Fov>,
+    move_info: Rc<RefCell<MoveInfo>>
 }
 
 impl<'a> Maps<'a> {
-    pub fn new(move_info: Rc<RefCell<MoveInfo>>) -> Maps<'a> {
-        let terrain = box Map::new(move_info.clone());
+    pub fn new(move_info: Rc<RefCell<MoveInfo>>, level: Level, up_stairs: Option<Point>) -> Maps<'a> {
+        let explored = box Explored::new(level.terrain.bounds());
+        let mut items = box ItemMap::new(level.terrain.bounds());
+        for &(point, kind) in level.items.iter() {
+            items.push(point, kind);
+        }
+        let mut terrain = box level.terrain;
+        terrain.set(level.exit, Tile::StairsDown);
+        match up_stairs {
+            Some(point) => terrain.set(point, Tile::StairsUp),
+            None => {}
+        }
+
         let enemies = box Map::new(move_info.clone());
         let friends = box Map::new(move_info.clone());
         let pcs = box Map::new(move_info.clone());
 
-        Maps {
+        let mut maps = Maps {
             friends: friends,
             enemies: enemies,
             terrain: terrain,
-            pcs: pcs
+            pcs: pcs,
+            items: items,
+            corpses: vec![],
+            explored: explored,
+            up_stairs: up_stairs,
+            down_stairs: level.exit,
+            flow: None,
+            travel: None,
+            heroine_fov: None,
+            move_info: move_info.clone()
+        };
+
+        for &(point, spawn) in level.spawns.iter() {
+            if maps.is_blocked(point) || (point.x == level.start.x && point.y == level.start.y) {
+                continue;
+            }
+            match spawn {
+                Spawn::Dog => maps.friends.push_actor(point, box Actor::dog(point.x, point.y, move_info.clone())),
+                Spawn::Cat => maps.friends.push_actor(point, box Actor::cat(point.x, point.y, move_info.clone())),
+                Spawn::Kobold(gear) => {
+                    let mut kobold = box Actor::kobold(point.x, point.y, move_info.clone());
+                    match gear {
+                        Some(kind) => {
+                            kobold.inventory.add(kind).ok();
+                            kobold.inventory.equip(0);
+                        },
+                        None => {}
+                    }
+                    maps.enemies.push_actor(point, kobold)
+                },
+                Spawn::Jackal => maps.enemies.push_actor(point, box Actor::jackal(point.x, point.y, move_info.clone()))
+            };
+        }
+
+        maps
+    }
+
+    pub fn layer(&self, layer: Layer) -> &Map<'a> {
+        match layer {
+            Layer::Pcs => &*self.pcs,
+            Layer::Friends => &*self.friends,
+            Layer::Enemies => &*self.enemies
+        }
+    }
+
+    pub fn layer_mut(&mut self, layer: Layer) -> &mut Map<'a> {
+        match layer {
+            Layer::Pcs => &mut *self.pcs,
+            Layer::Friends => &mut *self.friends,
+            Layer::Enemies => &mut *self.enemies
+        }
+    }
+
+    pub fn occupant_at(&self, point: Point) -> Option<(Layer, ActorId)> {
+        for &layer in LAYERS.iter() {
+            match self.layer(layer).ids_at(point).first() {
+                Some(&id) => return Some((layer, id)),
+                None => {}
+            }
+        }
+        None
+    }
+
+    pub fn is_occupied(&self, point: Point) -> bool {
+        self.occupant_at(point).is_some()
+    }
+
+    pub fn heroine(&self) -> Option<&Box<Actor>> {
+        self.pcs.first()
+    }
+
+    pub fn heroine_id(&self) -> Option<ActorId> {
+        self.pcs.ids().first().map(|&id| id)
+    }
+
+    pub fn is_heroine_dead(&self) -> bool {
+        self.pcs.is_empty()
+    }
+
+    // Rolls on the game's one RNG, so the same seed and keypresses always play out the same.
+    pub fn roll<T>(&self, f: |&mut GameRng| -> T) -> T {
+        let mut move_info = self.move_info.borrow_mut();
+        f(&mut move_info.deref_mut().rng)
+    }
+
+    pub fn is_blocked(&self, point: Point) -> bool {
+        !self.terrain.is_passable(point) || self.is_occupied(point)
+    }
+
+    // Moves whoever stands on `point` to the nearest free ground, so the heroine can
+    // arrive there. Anyone with nowhere to go is left where they are.
+    pub fn clear_cell(&mut self, point: Point) {
+        let (layer, id) = match self.occupant_at(point) {
+            Some(occupant) => occupant,
+            None => return
+        };
+        match self.nearest_free(point) {
+            Some(free) => self.layer_mut(layer).move_actor(id, free),
+            None => {}
+        }
+    }
+
+    // Searches outward one ring of cells at a time.
+    fn nearest_free(&self, point: Point) -> Option<Point> {
+        let bounds = self.terrain.bounds();
+        for radius in range(1, max(bounds.width(), bounds.height())) {
+            for dx in range(-radius, radius + 1) {
+                for dy in range(-radius, radius + 1) {
+                    let on_ring = dx == -radius || dx == radius || dy == -radius || dy == radius;
+                    let candidate = Point::new(point.x + dx, point.y + dy);
+                    if on_ring && !self.is_blocked(candidate) {
+                        return Some(candidate);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    // Carries out one of the heroine's actions. Returns whether it took any time; an
+    // action that couldn't happen, like walking into a wall, costs nothing.
+    pub fn perform(&mut self, action: Action, windows: &mut Windows) -> bool {
+        let id = match self.heroine_id() {
+            Some(id) => id,
+            None => return false
+        };
+
+        match action {
+            Action::Move(direction) => self.step(id, direction, windows),
+            Action::Wait => true,
+            Action::PickUp => self.pick_up(windows),
+            Action::Drop(index) => self.drop_item(index, windows),
+            Action::Equip(index) => self.toggle_equipped(index, windows),
+            Action::Use(kind, direction) => kind.use_in_direction(self, direction, windows),
+            Action::Throw(kind, direction) => kind.throw_in_direction(self, direction, windows),
+            // Changing levels is up to the dungeon, see `Dungeon::perform`.
+            Action::Descend | Action::Ascend | Action::Quit => false,
+            // Every step of the way ends its own turn, so there's none left over to end.
+            Action::Travel => {
+                self.travel_to_stairs(windows);
+                false
+            }
+        }
+    }
+
+    // Only rebuilds the maps when the heroine has moved or the terrain has changed,
+    // however many monsters read them in between.
+    pub fn refresh_flow_maps(&mut self) {
+        let goal = match self.heroine() {
+            Some(heroine) => heroine.position,
+            None => return
+        };
+        let is_stale = match self.flow {
+            Some(ref flow) => flow.is_stale(&*self.terrain, goal),
+            None => true
+        };
+        if is_stale {
+            self.flow = Some(FlowMaps::new(&*self.terrain, goal));
+        }
+    }
+
+    // Also where she remembers what she has seen, so exploring doesn't wait on drawing.
+    // Once she is gone her last view is kept, so the level stays drawn behind a game over.
+    pub fn refresh_heroine_fov(&mut self) {
+        let position = match self.heroine() {
+            Some(heroine) => heroine.position,
+            None => return
+        };
+        let fov = Fov::compute(&*self.terrain, position, SIGHT_RADIUS);
+        self.explored.remember(&fov);
+        self.heroine_fov = Some(fov);
+    }
+
+    // Like the flow maps, only rebuilt when something it was built from has changed.
+    fn refresh_travel_map(&mut self) {
+        let stairs = self.down_stairs;
+        let is_stale = match self.travel {
+            Some(ref travel) => travel.is_stale(&*self.terrain, &*self.explored, stairs),
+            None => true
+        };
+        if is_stale {
+            self.travel = Some(TravelMap::new(&*self.terrain, &*self.explored, stairs));
+        }
+    }
+
+    // Walks the heroine to the stairs down over ground she has explored, one turn at
+    // a time, stopping as soon as something hostile is in view or she gets hurt.
+    fn travel_to_stairs(&mut self, windows: &mut Windows) {
+        let stairs = self.down_stairs;
+        if !self.explored.is_explored(stairs) {
+            windows.messages.buffer_message("You haven't found the stairs down yet.");
+            return;
+        }
+
+        for _ in range(0, MAX_TRAVEL_STEPS) {
+            let (position, hp) = match self.heroine() {
+                Some(heroine) => (heroine.position, heroine.stats.hp),
+                None => return
+            };
+            if position.x == stairs.x && position.y == stairs.y {
+                windows.messages.buffer_message("You arrive at the stairs down.");
+                return;
+            }
+            if self.is_hostile_in_view() {
+                windows.messages.buffer_message("You can't travel with enemies in view.");
+                return;
+            }
+
+            // Each step may have shown her more of the way.
+            self.refresh_travel_map();
+            let next = match self.travel.as_ref().and_then(|travel| travel.map.downhill(position, |point| self.is_occupied(point))) {
+                Some(next) => next,
+                None => {
+                    windows.messages.buffer_message("Something is in the way.");
+                    return;
+                }
+            };
+            if !self.perform(Action::Move(Point::new(next.x - position.x, next.y - position.y)), windows) {
+                return;
+            }
+            self.end_turn(windows);
+
+            match self.heroine() {
+                Some(heroine) if heroine.stats.hp >= hp => {},
+                _ => return
+            }
         }
     }
 
-    pub fn update(&mut self, windows: &mut Windows) {
-        self.pcs.update(windows);
-        self.terrain.update(windows);
-        self.friends.update(windows);
-        self.enemies.update(windows);
+    // Goes by the view from her last turn rather than working out a fresh one.
+    fn is_hostile_in_view(&self) -> bool {
+        match self.heroine_fov {
+            Some(ref fov) => self.enemies.ids().iter().any(|&id| fov.is_visible(self.enemies.get(id).unwrap().position)),
+            None => false
+        }
     }
 
-    pub fn render(&mut self, renderer: &mut Box<RenderingComponent>) {
-        self.terrain.render(renderer);
-        self.friends.render(renderer);
-        self.enemies.render(renderer);
-        self.pcs.render(renderer);
+    // Charges the heroine for the action she just took, then lets time pass and
+    // everyone else act as their energy allows until she is ready to act again.
+    pub fn end_turn(&mut self, windows: &mut Windows) {
+        for id in self.pcs.ids().into_iter() {
+            scheduler::spend(self, Layer::Pcs, id);
+        }
+
+        self.refresh_flow_maps();
+        self.refresh_heroine_fov();
+        while !self.is_heroine_dead() {
+            match scheduler::next_ready(self) {
+                Some((Layer::Pcs, _)) => break,
+                Some((layer, id)) => {
+                    self.act(layer, id, windows);
+                    scheduler::spend(self, layer, id);
+                },
+                None => scheduler::tick(self)
+            }
+        }
+    }
+
+    fn act(&mut self, layer: Layer, id: ActorId, windows: &mut Windows) {
+        // The actor may have been killed earlier in this turn.
+        let (from, to) = match self.layer(layer).get(id) {
+            Some(actor) => (actor.position, actor.next_position(self, windows)),
+            None => return
+        };
+
+        if from.x != to.x || from.y != to.y {
+            self.move_to((layer, id), to, windows);
+        }
+    }
+
+    fn step(&mut self, id: ActorId, direction: Point, windows: &mut Windows) -> bool {
+        let to = match self.pcs.get(id) {
+            Some(heroine) => heroine.position.offset(direction),
+            None => return false
+        };
+        // Terrain outside the map counts as impassable, so this covers the edges too.
+        if !self.terrain.is_passable(to) {
+            windows.messages.buffer_message("You can't move that way!");
+            return false;
+        }
+        self.move_to((Layer::Pcs, id), to, windows)
+    }
+
+    // Moves into `to`, attacking a hostile occupant or, for the heroine, swapping with
+    // a friendly one. Returns whether anything happened.
+    fn move_to(&mut self, mover: (Layer, ActorId), to: Point, windows: &mut Windows) -> bool {
+        let (layer, id) = mover;
+        let acted = match self.occupant_at(to) {
+            None => {
+                self.layer_mut(layer).move_actor(id, to);
+                true
+            },
+            Some((other_layer, other_id)) => {
+                if layer.is_hostile_to(other_layer) {
+                    let weapon = self.layer(layer).get(id).unwrap().wielded_weapon();
+                    self.attack((layer, id), (other_layer, other_id), &weapon, windows);
+                    true
+                } else if layer == Layer::Pcs {
+                    self.swap_places((layer, id), (other_layer, other_id), windows);
+                    true
+                } else {
+                    false
+                }
+            }
+        };
+
+        if layer == Layer::Pcs {
+            match self.pcs.get(id) {
+                Some(heroine) => {
+                    let mut move_info = self.move_info.borrow_mut();
+                    move_info.deref_mut().char_location = heroine.position;
+                },
+                None => {}
+            }
+        }
+        acted
+    }
+
+    // Returns whether the heroine spent her turn picking something up.
+    pub fn pick_up(&mut self, windows: &mut Windows) -> bool {
+        let id = match self.heroine_id() {
+            Some(id) => id,
+            None => return false
+        };
+        let position = self.pcs.get(id).unwrap().position;
+        let kind = match self.items.take(position) {
+            Some(kind) => kind,
+            None => {
+                windows.messages.buffer_message("There is nothing here to pick up.");
+                return false;
+            }
+        };
+
+        let added = self.pcs.get_mut(id).unwrap().inventory.add(kind);
+        match added {
+            Ok(()) => {
+                let msg = format!("You pick up the {}.", kind.name());
+                windows.messages.buffer_message(msg.as_slice());
+                true
+            },
+            Err(kind) => {
+                self.items.push(position, kind);
+                windows.messages.buffer_message("Your pack is full.");
+                false
+            }
+        }
+    }
+
+    pub fn drop_item(&mut self, index: uint, windows: &mut Windows) -> bool {
+        let id = match self.heroine_id() {
+            Some(id) => id,
+            None => return false
+        };
+        let (position, removed) = {
+            let heroine = self.pcs.get_mut(id).unwrap();
+            (heroine.position, heroine.inventory.remove(index))
+        };
+        match removed {
+            Some(kind) => {
+                self.items.push(position, kind);
+                let msg = format!("You drop the {}.", kind.name());
+                windows.messages.buffer_message(msg.as_slice());
+                true
+            },
+            None => false
+        }
+    }
+
+    pub fn toggle_equipped(&mut self, index: uint, windows: &mut Windows) -> bool {
+        let id = match self.heroine_id() {
+            Some(id) => id,
+            None => return false
+        };
+        let mut messages: Vec<String> = vec![];
+        {
+            let inventory = &mut self.pcs.get_mut(id).unwrap().inventory;
+            let kind = match inventory.get(index) {
+                Some(kind) => kind,
+                None => return false
+            };
+            if inventory.is_equipped(index) {
+                inventory.unequip(index);
+                messages.push(format!("You take off your {}.", kind.name()));
+            } else {
+                match inventory.equip(index) {
+                    Some(removed) => {
+                        for other in removed.iter() {
+                            messages.push(format!("You take off your {}.", other.name()));
+                        }
+                        messages.push(format!("You equip your {}.", kind.name()));
+                    },
+                    None => {
+                        let msg = format!("You can't equip your {}.", kind.name());
+                        windows.messages.buffer_message(msg.as_slice());
+                        return false;
+                    }
+                }
+            }
+        }
+        for msg in messages.iter() {
+            windows.messages.buffer_message(msg.as_slice());
+        }
+        true
+    }
+
+    pub fn attack(&mut self, attacker: (Layer, ActorId), defender: (Layer, ActorId), weapon: &Weapon, windows: &mut Windows) {
+        let (attacker_layer, attacker_id) = attacker;
+        let (defender_layer, defender_id) = defender;
+        let (attacker_name, attacker_stats) = match self.layer(attacker_layer).get(attacker_id) {
+            Some(actor) => (actor.name.clone(), actor.effective_stats()),
+            None => return
+        };
+        let mut defender_stats = match self.layer(defender_layer).get(defender_id) {
+            Some(actor) => actor.effective_stats(),
+            None => return
+        };
+        let result = self.roll(|rng| combat::resolve_attack(rng, &attacker_stats, weapon, &mut defender_stats));
+        let defender_name = {
+            let actor = self.layer_mut(defender_layer).get_mut(defender_id).unwrap();
+            actor.stats.hp = defender_stats.hp;
+            actor.name.clone()
+        };
+
+        let msg = match (attacker_layer, defender_layer, result) {
+            (Layer::Pcs, _, AttackResult::Miss) =>
+                format!("You miss the {}.", defender_name),
+            (Layer::Pcs, _, AttackResult::Hit(damage)) | (Layer::Pcs, _, AttackResult::Kill(damage)) =>
+                format!("You hit the {} with your {} for {} damage.", defender_name, weapon.name, damage),
+            (_, Layer::Pcs, AttackResult::Miss) =>
+                format!("The {} misses you.", attacker_name),
+            (_, Layer::Pcs, AttackResult::Hit(damage)) | (_, Layer::Pcs, AttackResult::Kill(damage)) =>
+                format!("The {} hits you for {} damage.", attacker_name, damage),
+            (_, _, AttackResult::Miss) =>
+                format!("The {} misses the {}.", attacker_name, defender_name),
+            (_, _, AttackResult::Hit(damage)) | (_, _, AttackResult::Kill(damage)) =>
+                format!("The {} hits the {} for {} damage.", attacker_name, defender_name, damage)
+        };
+        windows.messages.buffer_message(msg.as_slice());
+
+        match result {
+            AttackResult::Kill(_) => self.kill(defender_layer, defender_id, windows),
+            _ => {}
+        }
+    }
+
+    pub fn kill(&mut self, layer: Layer, id: ActorId, windows: &mut Windows) {
+        let actor = match self.layer_mut(layer).remove(id) {
+            Some(actor) => actor,
+            None => return
+        };
+
+        let msg = match layer {
+            Layer::Pcs => "You die...".to_string(),
+            _ => format!("The {} dies!", actor.name)
+        };
+        windows.messages.buffer_message(msg.as_slice());
+        for carried in actor.inventory.items.iter() {
+            self.items.push(actor.position, carried.kind);
+        }
+        self.corpses.push(Corpse { position: actor.position, name: actor.name.clone() });
+    }
+
+    fn swap_places(&mut self, mover: (Layer, ActorId), other: (Layer, ActorId), windows: &mut Windows) {
+        let (mover_layer, mover_id) = mover;
+        let (other_layer, other_id) = other;
+        let from = self.layer(mover_layer).get(mover_id).unwrap().position;
+        let to = self.layer(other_layer).get(other_id).unwrap().position;
+
+        self.layer_mut(mover_layer).move_actor(mover_id, to);
+        self.layer_mut(other_layer).move_actor(other_id, from);
+
+        let msg = format!("You swap places with the {}.", self.layer(other_layer).get(other_id).unwrap().name);
+        windows.messages.buffer_message(msg.as_slice());
+    }
+
+    pub fn render(&self, renderer: &mut Box<RenderingComponent>, camera: &Camera) {
+        let fov = match self.heroine_fov {
+            Some(ref fov) => fov,
+            None => return
+        };
+
+        self.terrain.render(renderer, camera, fov, &*self.explored);
+        for corpse in self.corpses.iter() {
+            if !fov.is_visible(corpse.position) {
+                continue;
+            }
+            match camera.to_screen(corpse.position) {
+                Some(screen) => renderer.render_colored_object(screen, '%', Color::new(150u8, 20u8, 20u8)),
+                None => {}
+            }
+        }
+        self.items.render(renderer, camera, fov);
+        self.friends.render(renderer, camera, fov);
+        self.enemies.render(renderer, camera, fov);
+        self.pcs.render(renderer, camera, fov);
     }
 
     pub fn enemy_at(&self, point: Point) -> Option<&Box<Actor>> {
-        let enemies_at_point = &self.enemies.content[point.x as uint][point.y as uint];
-        if enemies_at_point.len() > 0 {
-            Some(&enemies_at_point[0])
-        } else {
-            None
+        self.enemies.actor_at(point)
+    }
+}
+
+// Items lying on the floor, stacked per cell with the most recently dropped on top.
+pub struct ItemMap {
+    items: Vec<Vec<Vec<ItemKind>>>,
+    size: Bound
+}
+
+impl ItemMap {
+    pub fn new(size: Bound) -> ItemMap {
+        let items = Vec::from_elem(size.width() as uint, Vec::from_elem(size.height() as uint, vec![]));
+        ItemMap { items: items, size: size }
+    }
+
+    pub fn push(&mut self, point: Point, kind: ItemKind) {
+        match self.size.grid_index(point) {
+            Some((x, y)) => self.items[x][y].push(kind),
+            None => panic!("({}, {}) is outside of the map", point.x, point.y)
+        }
+    }
+
+    pub fn take(&mut self, point: Point) -> Option<ItemKind> {
+        match self.size.grid_index(point) {
+            Some((x, y)) => self.items[x][y].pop(),
+            None => None
+        }
+    }
+
+    pub fn items_at(&self, point: Point) -> &[ItemKind] {
+        match self.size.grid_index(point) {
+            Some((x, y)) => self.items[x][y].as_slice(),
+            None => &[]
+        }
+    }
+
+    pub fn render(&self, renderer: &mut Box<RenderingComponent>, camera: &Camera, fov: &Fov) {
+        for screen in camera.viewport.cells() {
+            let point = camera.to_world(screen);
+            if !fov.is_visible(point) {
+                continue;
+            }
+            match self.items_at(point).last() {
+                Some(kind) => renderer.render_object(screen, kind.def().display_char),
+                None => {}
+            }
         }
     }
 }
 
+pub type ActorId = uint;
+
 pub struct Map<'a> {
-    pub content: Vec<Vec<Vec<Box<Actor>>>>,
+    actors: BTreeMap<ActorId, Box<Actor>>,
+    index: Vec<Vec<Vec<ActorId>>>,
+    next_id: ActorId,
     size: Bound,
     move_info: Rc<RefCell<MoveInfo>>
 }
@@ -64,60 +654,241 @@ impl<'a> Map<'a> {
         let size = {
             move_info.borrow().deref().bounds
         };
-        let content = Map::init_contents(size);
+        let index = Map::init_index(size);
         Map {
-            content: content,
+            actors: BTreeMap::new(),
+            index: index,
+            next_id: 0,
             size: size,
             move_info: move_info
         }
     }
 
-    pub fn init_contents(size: Bound) -> Vec<Vec<Vec<Box<Actor>>>> {
-        let mut contents: Vec<Vec<Vec<Box<Actor>>>> = vec![];
-        for _ in range(0, size.max.x) {
-            let mut x_vec: Vec<Vec<Box<Actor>>> = vec![];
-            for _ in range(0, size.max.y) {
-                let y_vec: Vec<Box<Actor>> = vec![];
-                x_vec.push(y_vec);
+    fn init_index(size: Bound) -> Vec<Vec<Vec<ActorId>>> {
+        let mut index: Vec<Vec<Vec<ActorId>>> = vec![];
+        for _ in range(0, size.width()) {
+            let mut x_vec: Vec<Vec<ActorId>> = vec![];
+            for _ in range(0, size.height()) {
+                x_vec.push(vec![]);
             }
-            contents.push(x_vec);
+            index.push(x_vec);
         }
 
-        contents
+        index
+    }
+
+    fn cell(&mut self, point: Point) -> &mut Vec<ActorId> {
+        let (x, y) = match self.size.grid_index(point) {
+            Some(index) => index,
+            None => panic!("({}, {}) is outside of the map", point.x, point.y)
+        };
+        &mut self.index[x][y]
     }
 
-    pub fn push_actor(&mut self, point: Point, actor: Box<Actor>) {
-        self.content[point.x as uint][point.y as uint].push(actor);
+    pub fn push_actor(&mut self, point: Point, actor: Box<Actor>) -> ActorId {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let mut actor = actor;
+        actor.position = point;
+        actor.arrival = {
+            let mut move_info = self.move_info.borrow_mut();
+            move_info.deref_mut().arrivals += 1;
+            move_info.deref().arrivals
+        };
+        self.actors.insert(id, actor);
+        self.cell(point).push(id);
+        id
     }
 
-    pub fn update(&mut self, windows: &mut Windows) {
-        let mut new_content = Map::init_contents(self.size);
-        for x_iter in self.content.iter_mut() {
-            for y_iter in x_iter.iter_mut() {
-                for actor in y_iter.iter_mut() {
-                    actor.update(windows);
-                    if actor.is_pc {
-                        {  let mut move_info = self.move_info.borrow_mut();
-                           move_info.deref_mut().char_location = actor.position
-                        };
-                    }
-                    let point = actor.position;
-                    let new_actor = actor.clone();
-                    new_content[point.x as uint][point.y as uint].push(new_actor);
-                }
-            }
+    pub fn remove(&mut self, id: ActorId) -> Option<Box<Actor>> {
+        let actor = self.actors.remove(&id);
+        match actor {
+            Some(ref actor) => self.cell(actor.position).retain(|&other| other != id),
+            None => {}
         }
-        self.content = new_content;
+        actor
     }
 
-    pub fn render(&mut self, renderer: &mut Box<RenderingComponent>) {
-        for (x, x_iter) in  self.content.iter_mut().enumerate() {
-            for (y, y_iter) in  x_iter.iter_mut().enumerate() {
-                for actor in y_iter.iter_mut() {
-                    let point = Point::new(x as i32, y as i32);
-                    renderer.render_object(point, actor.display_char);
-                }
+    pub fn remove_actor(&mut self, point: Point) -> Option<Box<Actor>> {
+        let id = match self.ids_at(point).last() {
+            Some(&id) => id,
+            None => return None
+        };
+        self.remove(id)
+    }
+
+    pub fn get(&self, id: ActorId) -> Option<&Box<Actor>> {
+        self.actors.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: ActorId) -> Option<&mut Box<Actor>> {
+        self.actors.get_mut(&id)
+    }
+
+    pub fn first(&self) -> Option<&Box<Actor>> {
+        self.actors.values().next()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actors.is_empty()
+    }
+
+    pub fn ids(&self) -> Vec<ActorId> {
+        self.actors.keys().map(|&id| id).collect()
+    }
+
+    pub fn ids_at(&self, point: Point) -> &[ActorId] {
+        match self.size.grid_index(point) {
+            Some((x, y)) => self.index[x][y].as_slice(),
+            None => &[]
+        }
+    }
+
+    pub fn actor_at(&self, point: Point) -> Option<&Box<Actor>> {
+        match self.ids_at(point).first() {
+            Some(id) => self.actors.get(id),
+            None => None
+        }
+    }
+
+    pub fn move_actor(&mut self, id: ActorId, to: Point) {
+        let from = match self.actors.get_mut(&id) {
+            Some(actor) => {
+                let from = actor.position;
+                actor.position = to;
+                from
+            },
+            None => return
+        };
+
+        self.reindex(id, from, to);
+    }
+
+    fn reindex(&mut self, id: ActorId, from: Point, to: Point) {
+        if from.x == to.x && from.y == to.y {
+            return;
+        }
+        self.cell(from).retain(|&other| other != id);
+        self.cell(to).push(id);
+    }
+
+    pub fn render(&self, renderer: &mut Box<RenderingComponent>, camera: &Camera, fov: &Fov) {
+        for actor in self.actors.values() {
+            if !fov.is_visible(actor.position) {
+                continue;
+            }
+            match camera.to_screen(actor.position) {
+                Some(screen) => renderer.render_object(screen, actor.display_char),
+                None => {}
             }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::{ Map, Maps, ActorId };
+    use actor::Actor;
+    use game::test::windows;
+    use mapgen::Level;
+    use movement::MoveInfo;
+    use terrain::{ Terrain, Tile };
+    use util::{ Bound, Point };
+
+    fn edge_path(bounds: Bound) -> Vec<Point> {
+        let mut path: Vec<Point> = vec![];
+        for x in range(bounds.min.x, bounds.max.x + 1) {
+            path.push(Point::new(x, bounds.min.y));
+        }
+        for y in range(bounds.min.y + 1, bounds.max.y + 1) {
+            path.push(Point::new(bounds.max.x, y));
+        }
+        for x in range(bounds.min.x, bounds.max.x).rev() {
+            path.push(Point::new(x, bounds.max.y));
+        }
+        for y in range(bounds.min.y + 1, bounds.max.y).rev() {
+            path.push(Point::new(bounds.min.x, y));
+        }
+        path
+    }
+
+    fn map_with_bounds(bounds: Bound) -> (Map<'static>, Rc<RefCell<MoveInfo>>) {
+        let move_info = Rc::new(RefCell::new(MoveInfo::new(bounds, bounds.min)));
+        (Map::new(move_info.clone()), move_info)
+    }
+
+    // Open floor right up to the edge, with the heroine in the `min` corner.
+    fn open_maps(bounds: Bound) -> (Maps<'static>, ActorId) {
+        let move_info = Rc::new(RefCell::new(MoveInfo::new(bounds, bounds.min)));
+        let level = Level {
+            terrain: Terrain::filled(bounds, Tile::Floor),
+            start: bounds.min,
+            exit: bounds.max,
+            spawns: vec![],
+            items: vec![]
+        };
+        let mut maps = Maps::new(move_info.clone(), level, None);
+        let id = maps.pcs.push_actor(bounds.min, box Actor::heroine(move_info));
+        (maps, id)
+    }
+
+    // Steps the heroine round the edge, trying to step off the map at every cell.
+    fn walk_every_edge(bounds: Bound) {
+        let (mut maps, id) = open_maps(bounds);
+        let mut windows = windows();
+        let mut from = bounds.min;
+
+        for &point in edge_path(bounds).iter().skip(1) {
+            assert!(maps.step(id, Point::new(point.x - from.x, point.y - from.y), &mut windows));
+            assert_eq!(maps.pcs.ids_at(point), [id].as_slice());
+            assert_eq!(maps.pcs.ids_at(from).len(), 0);
+
+            let outward = Point::new(
+                if point.x == bounds.min.x { -1 } else if point.x == bounds.max.x { 1 } else { 0 },
+                if point.y == bounds.min.y { -1 } else if point.y == bounds.max.y { 1 } else { 0 }
+            );
+            assert!(!maps.step(id, outward, &mut windows));
+            assert_eq!(maps.heroine().unwrap().position.x, point.x);
+            assert_eq!(maps.heroine().unwrap().position.y, point.y);
+            from = point;
+        }
+    }
+
+    #[test]
+    fn heroine_walks_every_edge() {
+        walk_every_edge(Bound::new(0, 0, 78, 49));
+    }
+
+    #[test]
+    fn heroine_walks_every_edge_of_an_offset_map() {
+        walk_every_edge(Bound::new(5, 3, 14, 9));
+    }
+
+    #[test]
+    fn cells_past_the_edge_are_empty() {
+        let bounds = Bound::new(0, 0, 9, 5);
+        let (map, _) = map_with_bounds(bounds);
+
+        assert_eq!(map.ids_at(Point::new(10, 0)).len(), 0);
+        assert_eq!(map.ids_at(Point::new(0, 6)).len(), 0);
+        assert_eq!(map.ids_at(Point::new(-1, 0)).len(), 0);
+        assert!(map.actor_at(Point::new(10, 6)).is_none());
+    }
+
+    #[test]
+    fn terrain_edges_are_walls_inside_the_bound() {
+        let bounds = Bound::new(0, 0, 78, 49);
+        let terrain = Terrain::new(bounds);
+
+        for &point in edge_path(bounds).iter() {
+            assert!(terrain.get(point).is_some());
+            assert!(!terrain.is_passable(point));
+        }
+        assert!(terrain.get(Point::new(79, 49)).is_none());
+        assert!(terrain.is_passable(Point::new(77, 48)));
+    }
+}
@@ -1,169 +1,360 @@
-extern crate tcod;
-
-use std;
 use std::cell::RefCell;
 use std::rc::Rc;
-use std::rand::Rng;
+use std::rand::{ Rng, XorShiftRng };
 
-use util::{Bound, Point, XPointRelation, YPointRelation, PointRelation };
-use util::Contains::{DoesContain, DoesNotContain};
+use util::{ Bound, Point };
+use util::Contains::DoesContain;
 use game::Windows;
-use input::{ KeyCode, KeyboardInput };
-use input::Key::SpecialKey;
+use maps::Maps;
+use pathfinding;
+use dijkstra;
+use awareness::{ Awareness, State };
+use input::KeyboardInput;
+use mapgen::rng_from_seed;
+
+// The game's RNG. It counts the numbers it hands out so that a saved game can wind a
+// fresh one on to the same place, since XorShiftRng keeps its state to itself.
+pub struct GameRng {
+    seed: u32,
+    draws: u64,
+    rng: XorShiftRng
+}
+
+impl GameRng {
+    pub fn new(seed: u32) -> GameRng {
+        GameRng { seed: seed, draws: 0, rng: rng_from_seed(seed) }
+    }
+
+    // Picks up where an RNG with the same seed was after `draws` numbers.
+    pub fn resume(seed: u32, draws: u64) -> GameRng {
+        let mut rng = GameRng::new(seed);
+        for _ in range(0, draws) {
+            rng.next_u32();
+        }
+        rng
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    pub fn draws(&self) -> u64 {
+        self.draws
+    }
+}
+
+impl Rng for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.draws += 1;
+        self.rng.next_u32()
+    }
+}
 
 pub struct MoveInfo {
     pub last_keypress: Option<KeyboardInput>,
     pub char_location: Point,
-    pub bounds: Bound
+    pub bounds: Bound,
+    // The game's one source of randomness after map generation, see `Maps::roll`.
+    pub rng: GameRng,
+    // How many actors have been put on any level so far, see `Map::push_actor`.
+    pub arrivals: uint
 }
 
 impl MoveInfo {
-    pub fn new(bound: Bound) -> MoveInfo {
+    pub fn new(bound: Bound, char_location: Point) -> MoveInfo {
+        MoveInfo::with_seed(bound, char_location, 0)
+    }
+
+    pub fn with_seed(bound: Bound, char_location: Point, seed: u32) -> MoveInfo {
         MoveInfo {
             last_keypress: None,
-            char_location: Point::new(40, 25),
-            bounds: bound
+            char_location: char_location,
+            bounds: bound,
+            rng: GameRng::new(seed),
+            arrivals: 0
         }
     }
 }
 
 pub trait MovementComponent {
     fn new(Rc<RefCell<MoveInfo>>) -> Self;
-    fn update(&self, Point, &mut Windows) -> Point;
+    fn update(&self, Point, &Maps, &mut Windows) -> Point;
     fn box_clone(&self) -> Box<MovementComponent + 'static>;
+    // The name save files know this component by, see `from_registry`.
+    fn registry_name(&self) -> &'static str;
+    fn awareness(&self) -> Option<&Awareness> { None }
 }
 
-pub struct RandomMovementComponent {
-    window_bounds: Bound,
-    move_info: Rc<RefCell<MoveInfo>>
+fn build<T: MovementComponent + 'static>(move_info: Rc<RefCell<MoveInfo>>) -> Box<MovementComponent + 'static> {
+    let mc: Box<T> = box MovementComponent::new(move_info);
+    mc as Box<MovementComponent>
 }
 
-impl MovementComponent for RandomMovementComponent {
-    fn new(move_info: Rc<RefCell<MoveInfo>>) -> RandomMovementComponent {
-        let window_bounds = { move_info.borrow().deref().bounds };
-        RandomMovementComponent { window_bounds: window_bounds, move_info: move_info }
+// Rebuilds a movement component from the name it was saved under. Every component
+// needs an entry here or actors using it can't be loaded.
+pub fn from_registry(name: &str, move_info: Rc<RefCell<MoveInfo>>) -> Option<Box<MovementComponent + 'static>> {
+    match name {
+        "random" => Some(build::<RandomMovementComponent>(move_info)),
+        "user" => Some(build::<UserMovementComponent>(move_info)),
+        "agro" => Some(build::<AgroMovementComponent>(move_info)),
+        "hunter" => Some(build::<HunterMovementComponent>(move_info)),
+        "skittish" => Some(build::<SkittishMovementComponent>(move_info)),
+        _ => None
     }
+}
 
-    fn update(&self, point: Point, _: &mut Windows) -> Point {
-        let mut offset = Point { x: point.x, y: point.y };
+fn wander(bounds: Bound, point: Point, maps: &Maps) -> Point {
+    // Standing still is as likely as stepping in any one direction.
+    if maps.roll(|rng| rng.gen_range(0u, 9)) == 0 {
+        return point;
+    }
 
-        let offset_x: i32 = std::rand::task_rng().gen_range(0, 3) - 1;
-        match self.window_bounds.contains(offset.offset_x(offset_x)) {
-            DoesContain => offset = offset.offset_x(offset_x),
-            DoesNotContain => {}
-        }
+    let mut offsets = vec![
+        Point::new(-1, -1), Point::new(0, -1), Point::new(1, -1),
+        Point::new(-1, 0), Point::new(1, 0),
+        Point::new(-1, 1), Point::new(0, 1), Point::new(1, 1)
+    ];
+    maps.roll(|rng| rng.shuffle(offsets.as_mut_slice()));
 
-        let offset_y: i32 = std::rand::task_rng().gen_range(0, 3) - 1;
-        match self.window_bounds.contains(offset.offset_y(offset_y)) {
-            DoesContain => offset = offset.offset_y(offset_y),
-            DoesNotContain => {}
+    for &offset in offsets.iter() {
+        match bounds.contains(point.offset(offset)) {
+            DoesContain if !maps.is_blocked(point.offset(offset)) => return point.offset(offset),
+            _ => {}
         }
+    }
+
+    point
+}
+
+pub struct RandomMovementComponent {
+    bounds: Bound,
+    move_info: Rc<RefCell<MoveInfo>>
+}
+
+impl MovementComponent for RandomMovementComponent {
+    fn new(move_info: Rc<RefCell<MoveInfo>>) -> RandomMovementComponent {
+        let bounds = { move_info.borrow().deref().bounds };
+        RandomMovementComponent { bounds: bounds, move_info: move_info }
+    }
 
-        offset
+    fn update(&self, point: Point, maps: &Maps, _: &mut Windows) -> Point {
+        wander(self.bounds, point, maps)
     }
 
     fn box_clone(&self) -> Box<MovementComponent + 'static> {
         box RandomMovementComponent {
-            window_bounds: self.window_bounds,
+            bounds: self.bounds,
             move_info: self.move_info.clone()
         }
     }
+
+    fn registry_name(&self) -> &'static str { "random" }
 }
 
 pub struct UserMovementComponent {
-    window_bounds: Bound,
+    bounds: Bound,
     move_info: Rc<RefCell<MoveInfo>>
 }
 
 impl MovementComponent for UserMovementComponent {
     fn new(move_info: Rc<RefCell<MoveInfo>>) -> UserMovementComponent {
-        let window_bounds = { move_info.borrow().deref().bounds };
-        UserMovementComponent { window_bounds: window_bounds, move_info: move_info }
-    }
-
-    fn update(&self, point: Point, windows: &mut Windows) -> Point {
-        let mut offset = Point { x: point.x, y: point.y };
-        let last_keypress = { self.move_info.borrow().deref().last_keypress };
-        offset = match last_keypress {
-            Some(keypress) => {
-                match keypress.key {
-                    SpecialKey(KeyCode::Up) => {
-                        offset.offset_y(-1)
-                    },
-                    SpecialKey(KeyCode::Down) => {
-                        offset.offset_y(1)
-                    },
-                    SpecialKey(KeyCode::Left) => {
-                        offset.offset_x(-1)
-                    },
-                    SpecialKey(KeyCode::Right) => {
-                        offset.offset_x(1)
-                    },
-                    _ => { offset }
-                }
-            },
-            None => { offset }
-        };
+        let bounds = { move_info.borrow().deref().bounds };
+        UserMovementComponent { bounds: bounds, move_info: move_info }
+    }
 
-        match self.window_bounds.contains(offset) {
-            DoesContain => { offset }
-            DoesNotContain => {
-                windows.messages.buffer_message("You can't move that way!");
-                point
-            }
-        }
+    // The heroine never decides on a move by herself; her keypresses become
+    // actions that `Maps::perform` carries out.
+    fn update(&self, point: Point, _: &Maps, _: &mut Windows) -> Point {
+        point
     }
 
     fn box_clone(&self) -> Box<MovementComponent + 'static> {
         box UserMovementComponent {
-            window_bounds: self.window_bounds,
+            bounds: self.bounds,
             move_info: self.move_info.clone()
         }
     }
+
+    fn registry_name(&self) -> &'static str { "user" }
 }
 
+// Heads for `target` along an A* path. Other actors are only soft obstacles, so a
+// kobold will still queue up behind a friend in a corridor rather than wander off.
+// Reaching the heroine's cell is a bump attack, which Maps resolves.
+fn step_towards(bounds: Bound, point: Point, target: Point, maps: &Maps) -> Point {
+    let path = pathfinding::find_path(&*maps.terrain, point, target, |other| maps.is_occupied(other));
+    let next = match path {
+        Some(path) => match path.first() {
+            Some(&next) => next,
+            None => point
+        },
+        None => point
+    };
+
+    match bounds.contains(next) {
+        DoesContain => next,
+        _ => point
+    }
+}
+
+// Goes after the heroine once it has seen or heard her, and otherwise wanders or sleeps.
 pub struct AgroMovementComponent {
-    window_bounds: Bound,
+    bounds: Bound,
+    awareness: Awareness,
     move_info: Rc<RefCell<MoveInfo>>
 }
 
 impl MovementComponent for AgroMovementComponent {
     fn new(move_info: Rc<RefCell<MoveInfo>>) -> AgroMovementComponent {
-        let window_bounds = { move_info.borrow().deref().bounds };
-        AgroMovementComponent { window_bounds: window_bounds, move_info: move_info}
+        let bounds = { move_info.borrow().deref().bounds };
+        let awareness = Awareness::sometimes_asleep(&mut move_info.borrow_mut().deref_mut().rng);
+        AgroMovementComponent { bounds: bounds, awareness: awareness, move_info: move_info }
+    }
+
+    fn update(&self, point: Point, maps: &Maps, _: &mut Windows) -> Point {
+        self.awareness.update(point, maps);
+        match (self.awareness.state(), self.awareness.target()) {
+            (State::Asleep, _) => point,
+            (_, Some(target)) => step_towards(self.bounds, point, target, maps),
+            (_, None) => wander(self.bounds, point, maps)
+        }
+    }
+
+    fn box_clone(&self) -> Box<MovementComponent + 'static> {
+        box AgroMovementComponent {
+            bounds: self.bounds,
+            awareness: self.awareness.clone(),
+            move_info: self.move_info.clone()
+        }
     }
 
-    fn update(&self, point: Point, _: &mut Windows) -> Point {
-        let char_point = { self.move_info.borrow().deref().char_location };
-        let mut offset = Point { x: 0, y: 0 };
+    fn registry_name(&self) -> &'static str { "agro" }
+
+    fn awareness(&self) -> Option<&Awareness> { Some(&self.awareness) }
+}
+
+// Hunts the heroine down the shared chase map, so a whole pack closes in from every
+// side instead of queueing up behind each other. Once she is out of sight and earshot
+// it falls back to searching where she was last noticed.
+pub struct HunterMovementComponent {
+    bounds: Bound,
+    awareness: Awareness,
+    move_info: Rc<RefCell<MoveInfo>>
+}
+
+impl MovementComponent for HunterMovementComponent {
+    fn new(move_info: Rc<RefCell<MoveInfo>>) -> HunterMovementComponent {
+        let bounds = { move_info.borrow().deref().bounds };
+        HunterMovementComponent { bounds: bounds, awareness: Awareness::new(State::Wandering), move_info: move_info }
+    }
 
-        match point.compare_x(char_point) {
-            XPointRelation::RightOfPoint => offset = offset.offset_x(-1),
-            XPointRelation::LeftOfPoint => offset = offset.offset_x(1),
-            XPointRelation::OnPointX => {}
+    fn update(&self, point: Point, maps: &Maps, _: &mut Windows) -> Point {
+        self.awareness.update(point, maps);
+        let target = match self.awareness.target() {
+            Some(target) => target,
+            None => return wander(self.bounds, point, maps)
+        };
+        if self.awareness.state() == State::Searching {
+            return step_towards(self.bounds, point, target, maps);
         }
 
-        match point.compare_y(char_point) {
-            YPointRelation::BelowPoint => offset = offset.offset_y(-1),
-            YPointRelation::AbovePoint => offset = offset.offset_y(1),
-            YPointRelation::OnPointY => {}
+        // While hunting, the target is where the heroine is right now, which is
+        // exactly what the chase map leads to.
+        let step = match maps.flow {
+            Some(ref flow) => flow.chase.downhill(point, |other| {
+                // Stepping onto the heroine is a bump attack, anyone else is in the way.
+                maps.is_occupied(other) && (other.x != target.x || other.y != target.y)
+            }),
+            None => None
+        };
+
+        match step {
+            Some(next) => next,
+            None => wander(self.bounds, point, maps)
         }
+    }
 
-        match point.offset(offset).compare(char_point) {
-            PointRelation::PointsEqual => { point },
-            PointRelation::PointsNotEqual => {
-                match self.window_bounds.contains(point.offset(offset)) {
-                    DoesContain => { point.offset(offset) },
-                    DoesNotContain => { point }
-                }
-            }
+    fn box_clone(&self) -> Box<MovementComponent + 'static> {
+        box HunterMovementComponent {
+            bounds: self.bounds,
+            awareness: self.awareness.clone(),
+            move_info: self.move_info.clone()
+        }
+    }
+
+    fn registry_name(&self) -> &'static str { "hunter" }
+
+    fn awareness(&self) -> Option<&Awareness> { Some(&self.awareness) }
+}
+
+// Wanders about, but bolts down the fleeing map whenever the heroine gets close.
+pub struct SkittishMovementComponent {
+    bounds: Bound,
+    move_info: Rc<RefCell<MoveInfo>>
+}
+
+static SKITTISH_DISTANCE: i32 = 4;
+
+impl MovementComponent for SkittishMovementComponent {
+    fn new(move_info: Rc<RefCell<MoveInfo>>) -> SkittishMovementComponent {
+        let bounds = { move_info.borrow().deref().bounds };
+        SkittishMovementComponent { bounds: bounds, move_info: move_info }
+    }
+
+    fn update(&self, point: Point, maps: &Maps, _: &mut Windows) -> Point {
+        let step = match maps.flow {
+            Some(ref flow) => match flow.chase.distance(point) {
+                Some(distance) if distance <= SKITTISH_DISTANCE * dijkstra::STEP =>
+                    flow.flee.downhill(point, |other| maps.is_occupied(other)),
+                _ => None
+            },
+            None => None
+        };
+
+        match step {
+            Some(next) => next,
+            None => wander(self.bounds, point, maps)
         }
     }
 
     fn box_clone(&self) -> Box<MovementComponent + 'static> {
-        box AgroMovementComponent {
-            window_bounds: self.window_bounds,
+        box SkittishMovementComponent {
+            bounds: self.bounds,
             move_info: self.move_info.clone()
         }
     }
+
+    fn registry_name(&self) -> &'static str { "skittish" }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::{ MoveInfo, wander };
+    use maps::Maps;
+    use mapgen::Level;
+    use terrain::Terrain;
+    use util::{ Bound, Point };
+
+    fn walk(seed: u32) -> Vec<(i32, i32)> {
+        let bounds = Bound::new(0, 0, 19, 9);
+        let move_info = Rc::new(RefCell::new(MoveInfo::with_seed(bounds, Point::new(1, 1), seed)));
+        let level = Level { terrain: Terrain::new(bounds), start: Point::new(1, 1), exit: Point::new(18, 8), spawns: vec![], items: vec![] };
+        let maps = Maps::new(move_info, level, None);
+
+        let mut point = Point::new(10, 5);
+        let mut steps = vec![];
+        for _ in range(0u, 30) {
+            point = wander(bounds, point, &maps);
+            steps.push((point.x, point.y));
+        }
+        steps
+    }
+
+    #[test]
+    fn wandering_repeats_for_the_same_seed() {
+        assert_eq!(walk(42), walk(42));
+    }
 }
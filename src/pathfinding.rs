@@ -0,0 +1,227 @@
+use std::cmp::{ max, Ordering };
+use std::collections::BinaryHeap;
+
+use util::{ Bound, Point };
+use terrain::Terrain;
+
+// Walking through a cell someone is standing in costs this much extra, so paths go
+// around actors when the detour is short but still squeeze past them otherwise.
+static OCCUPIED_PENALTY: uint = 5;
+// Gives up on far away or unreachable goals instead of searching the whole level.
+static MAX_EXPANDED: uint = 4000;
+
+static DIRECTIONS: [(i32, i32), ..8] = [
+    (-1, -1), (0, -1), (1, -1),
+    (-1, 0), (1, 0),
+    (-1, 1), (0, 1), (1, 1)
+];
+
+struct Node {
+    estimate: uint,
+    cost: uint,
+    point: Point
+}
+
+// BinaryHeap is a max heap, so the ordering is reversed to pop the cheapest node first.
+impl PartialEq for Node {
+    fn eq(&self, other: &Node) -> bool {
+        self.estimate == other.estimate && self.cost == other.cost
+    }
+}
+
+impl Eq for Node {}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Node) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Node) -> Ordering {
+        // Among equally promising nodes prefer the one furthest along.
+        match other.estimate.cmp(&self.estimate) {
+            Ordering::Equal => self.cost.cmp(&other.cost),
+            ordering => ordering
+        }
+    }
+}
+
+// Moving diagonally costs the same as moving straight, so the number of moves left
+// is the larger of the two distances.
+fn heuristic(from: Point, to: Point) -> uint {
+    max((from.x - to.x).abs(), (from.y - to.y).abs()) as uint
+}
+
+fn grid<T: Clone>(bounds: Bound, value: T) -> Vec<Vec<T>> {
+    Vec::from_elem(bounds.width() as uint, Vec::from_elem(bounds.height() as uint, value))
+}
+
+// Finds the cheapest 8-way path from `from` to `to` over passable terrain with A*.
+// `is_occupied` marks soft obstacles; the goal itself is never treated as one so
+// monsters can path right up to whoever they are chasing. The path leaves out
+// `from` and ends with `to`.
+pub fn find_path(terrain: &Terrain, from: Point, to: Point, is_occupied: |Point| -> bool) -> Option<Vec<Point>> {
+    let bounds = terrain.bounds();
+    if bounds.grid_index(from).is_none() || !terrain.is_passable(to) {
+        return None;
+    }
+
+    let mut costs: Vec<Vec<Option<uint>>> = grid(bounds, None);
+    let mut came_from: Vec<Vec<Option<(i32, i32)>>> = grid(bounds, None);
+    let mut open = BinaryHeap::new();
+
+    let (x, y) = bounds.grid_index(from).unwrap();
+    costs[x][y] = Some(0);
+    open.push(Node { estimate: heuristic(from, to), cost: 0, point: from });
+
+    let mut expanded = 0u;
+    while let Some(node) = open.pop() {
+        let point = node.point;
+        if point.x == to.x && point.y == to.y {
+            return Some(walk_back(bounds, &came_from, from, to));
+        }
+
+        let (x, y) = bounds.grid_index(point).unwrap();
+        // Skip entries left behind after a cheaper way to this cell was found.
+        match costs[x][y] {
+            Some(cost) if cost < node.cost => continue,
+            _ => {}
+        }
+
+        expanded += 1;
+        if expanded > MAX_EXPANDED {
+            return None;
+        }
+
+        for &(dx, dy) in DIRECTIONS.iter() {
+            let next = Point::new(point.x + dx, point.y + dy);
+            if !terrain.is_passable(next) {
+                continue;
+            }
+            let is_goal = next.x == to.x && next.y == to.y;
+            let step = if !is_goal && is_occupied(next) { 1 + OCCUPIED_PENALTY } else { 1 };
+            let cost = node.cost + step;
+
+            let (nx, ny) = bounds.grid_index(next).unwrap();
+            match costs[nx][ny] {
+                Some(known) if known <= cost => continue,
+                _ => {}
+            }
+            costs[nx][ny] = Some(cost);
+            came_from[nx][ny] = Some((point.x, point.y));
+            open.push(Node { estimate: cost + heuristic(next, to), cost: cost, point: next });
+        }
+    }
+
+    None
+}
+
+fn walk_back(bounds: Bound, came_from: &Vec<Vec<Option<(i32, i32)>>>, from: Point, to: Point) -> Vec<Point> {
+    let mut path = vec![];
+    let mut point = to;
+    while point.x != from.x || point.y != from.y {
+        path.push(point);
+        let (x, y) = bounds.grid_index(point).unwrap();
+        let (px, py) = came_from[x][y].unwrap();
+        point = Point::new(px, py);
+    }
+    path.reverse();
+    path
+}
+
+#[cfg(test)]
+mod test {
+    use super::find_path;
+    use terrain::{ Terrain, Tile };
+    use util::{ Bound, Point };
+
+    // Builds terrain from rows of '#' walls and '.' floor.
+    fn terrain_from(rows: &[&str]) -> Terrain {
+        let bounds = Bound::new(0, 0, rows[0].len() as i32 - 1, rows.len() as i32 - 1);
+        let mut terrain = Terrain::filled(bounds, Tile::Floor);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if c == '#' {
+                    terrain.set(Point::new(x as i32, y as i32), Tile::Wall);
+                }
+            }
+        }
+        terrain
+    }
+
+    #[test]
+    fn straight_line_in_the_open() {
+        let terrain = terrain_from(&[
+            "#######",
+            "#.....#",
+            "#######"
+        ]);
+        let path = find_path(&terrain, Point::new(1, 1), Point::new(5, 1), |_| false).unwrap();
+        assert_eq!(path.len(), 4);
+        assert_eq!(path[3].x, 5);
+        assert!(path.iter().all(|point| point.y == 1));
+    }
+
+    #[test]
+    fn walks_around_a_wall() {
+        let terrain = terrain_from(&[
+            "#######",
+            "#..#..#",
+            "#..#..#",
+            "#.....#",
+            "#######"
+        ]);
+        let path = find_path(&terrain, Point::new(1, 1), Point::new(5, 1), |_| false).unwrap();
+        // Down to the gap under the wall and back up, moving diagonally where it can.
+        assert_eq!(path.len(), 4);
+        assert!(path.iter().any(|point| point.x == 3 && point.y == 3));
+        assert!(path.iter().all(|point| terrain.is_passable(*point)));
+    }
+
+    #[test]
+    fn no_path_into_a_sealed_room() {
+        let terrain = terrain_from(&[
+            "#######",
+            "#..#..#",
+            "#..#..#",
+            "#######"
+        ]);
+        assert!(find_path(&terrain, Point::new(1, 1), Point::new(5, 2), |_| false).is_none());
+    }
+
+    #[test]
+    fn steps_around_an_actor_when_there_is_room() {
+        let terrain = terrain_from(&[
+            "#######",
+            "#.....#",
+            "#.....#",
+            "#######"
+        ]);
+        let path = find_path(&terrain, Point::new(1, 1), Point::new(5, 1), |point| point.x == 3 && point.y == 1).unwrap();
+        assert_eq!(path.len(), 4);
+        assert!(!path.iter().any(|point| point.x == 3 && point.y == 1));
+    }
+
+    #[test]
+    fn squeezes_past_an_actor_in_a_corridor() {
+        let terrain = terrain_from(&[
+            "#######",
+            "#.....#",
+            "#######"
+        ]);
+        let path = find_path(&terrain, Point::new(1, 1), Point::new(5, 1), |point| point.x == 3 && point.y == 1).unwrap();
+        assert_eq!(path.len(), 4);
+    }
+
+    #[test]
+    fn the_goal_is_never_an_obstacle() {
+        let terrain = terrain_from(&[
+            "#####",
+            "#...#",
+            "#####"
+        ]);
+        let path = find_path(&terrain, Point::new(1, 1), Point::new(3, 1), |point| point.x == 3).unwrap();
+        assert_eq!(path.len(), 2);
+    }
+}
@@ -1,29 +1,48 @@
+#[cfg(feature = "tcod")]
 extern crate tcod;
-use self::tcod::{ Console, KeyState, BackgroundFlag };
+#[cfg(feature = "tcod")]
+use self::tcod::{ Console, KeyState, BackgroundFlag, TextAlignment };
+
+use std::cell::RefCell;
+use std::io::process::{ Command, StdioContainer };
+use std::io::stdio;
+use std::rc::Rc;
 
 use util::{Point, Bound};
-use input::{ InputComponent, TcodInputComponent, KeyboardInput};
+use color::Color;
+use input::{ InputComponent, TerminalInputComponent, KeyboardInput, KeyCode };
+#[cfg(feature = "tcod")]
+use input::TcodInputComponent;
+use input::Key::SpecialKey;
 use window::WindowComponent;
 
-pub struct TcodRenderingComponent<'a> {
-    pub console: Console,
-    pub input_component: Box<InputComponent<KeyState> + 'a>
-}
-
 pub trait RenderingComponent {
     fn new(Bound) -> Self;
     fn before_render_new_frame(&mut self);
     fn render_object(&mut self, Point, char);
+    fn render_colored_object(&mut self, Point, char, Color);
     fn after_render_new_frame(&mut self);
     fn wait_for_keypress(&mut self) -> KeyboardInput;
     fn attach_window(&mut self, &mut Box<WindowComponent>);
 }
 
+#[cfg(feature = "tcod")]
+pub struct TcodRenderingComponent<'a> {
+    pub console: Console,
+    pub input_component: Box<InputComponent<KeyState> + 'a>
+}
+
+#[cfg(feature = "tcod")]
+fn tcod_color(color: Color) -> tcod::Color {
+    tcod::Color::new(color.r, color.g, color.b)
+}
+
+#[cfg(feature = "tcod")]
 impl<'a> RenderingComponent for TcodRenderingComponent<'a> {
     fn new(bound: Bound) -> TcodRenderingComponent<'a> {
         let console = Console::init_root(
-            (bound.max.x + 1) as int,
-            (bound.max.y + 1) as int,
+            bound.width() as int,
+            bound.height() as int,
             "libtcod Rust tutorial",
             false
         );
@@ -42,6 +61,10 @@ impl<'a> RenderingComponent for TcodRenderingComponent<'a> {
         self.console.put_char(position.x as int, position.y as int, symbol, BackgroundFlag::Set);
     }
 
+    fn render_colored_object(&mut self, position: Point, symbol: char, color: Color) {
+        self.console.put_char_ex(position.x as int, position.y as int, symbol, tcod_color(color), tcod::Color::new(0u8, 0u8, 0u8));
+    }
+
     fn after_render_new_frame(&mut self) {
         Console::flush();
     }
@@ -52,20 +75,273 @@ impl<'a> RenderingComponent for TcodRenderingComponent<'a> {
     }
 
     fn attach_window(&mut self, window: &mut Box<WindowComponent>) {
-        window.clear();
-        let mut line = 0i;
-        let bounds  = window.get_bounds();
-        let messages = window.get_messages();
+        let bounds = window.get_bounds();
+        self.console.set_default_background(tcod_color(window.get_bg_color()));
+        self.console.rect(bounds.min.x as int, bounds.min.y as int, bounds.width() as int,
+        bounds.height() as int, true, BackgroundFlag::Set);
+
+        for (line, text) in window.visible_lines().iter().enumerate() {
+            self.console.print_ex(bounds.min.x as int, bounds.min.y as int + line as int,
+            BackgroundFlag::Set, TextAlignment::Left, text.as_slice());
+        }
+        self.console.set_default_background(tcod::Color::new(0u8, 0u8, 0u8));
+    }
+}
+
+#[deriving(Clone, PartialEq)]
+struct TerminalCell {
+    symbol: char,
+    fg: Color,
+    bg: Color
+}
+
+static TERMINAL_FG: Color = Color { r: 255, g: 255, b: 255 };
+static TERMINAL_BG: Color = Color { r: 0, g: 0, b: 0 };
+
+// Runs stty on the terminal we were started from, returning what it printed.
+fn stty(args: &[&str]) -> Option<String> {
+    match Command::new("stty").args(args).stdin(StdioContainer::InheritFd(0)).output() {
+        Ok(ref output) if output.status.success() =>
+            Some(String::from_utf8_lossy(output.output.as_slice()).into_string().as_slice().trim().to_string()),
+        _ => None
+    }
+}
+
+// Draws straight to the terminal with ANSI escape sequences, for playing over SSH where
+// no window can be opened. The terminal is put in raw mode for as long as this lives,
+// and needs to be at least as big as the screen bounds. Colours are sent as 24 bit.
+pub struct TerminalRenderingComponent {
+    cells: Vec<Vec<TerminalCell>>,
+    bounds: Bound,
+    // How to put the terminal back the way it was, from `stty -g`.
+    saved_mode: Option<String>,
+    pub input_component: Box<InputComponent<Vec<u8>> + 'static>
+}
+
+impl TerminalRenderingComponent {
+    fn put(&mut self, position: Point, symbol: char, fg: Color, bg: Option<Color>) {
+        match self.bounds.grid_index(position) {
+            Some((x, y)) => {
+                let cell = &mut self.cells[y][x];
+                cell.symbol = symbol;
+                cell.fg = fg;
+                match bg {
+                    Some(bg) => cell.bg = bg,
+                    None => {}
+                }
+            },
+            None => {}
+        }
+    }
+}
+
+impl RenderingComponent for TerminalRenderingComponent {
+    fn new(bound: Bound) -> TerminalRenderingComponent {
+        let saved_mode = stty(&["-g"]);
+        stty(&["raw", "-echo"]);
+        // Hide the cursor and start from a blank screen.
+        stdio::stdout_raw().write_str("\x1b[?25l\x1b[2J").ok();
+
+        let blank = TerminalCell { symbol: ' ', fg: TERMINAL_FG, bg: TERMINAL_BG };
+        let input_component: Box<TerminalInputComponent> = box InputComponent::new();
+        TerminalRenderingComponent {
+            cells: Vec::from_elem(bound.height() as uint, Vec::from_elem(bound.width() as uint, blank)),
+            bounds: bound,
+            saved_mode: saved_mode,
+            input_component: input_component
+        }
+    }
+
+    fn before_render_new_frame(&mut self) {
+        for row in self.cells.iter_mut() {
+            for cell in row.iter_mut() {
+                *cell = TerminalCell { symbol: ' ', fg: TERMINAL_FG, bg: TERMINAL_BG };
+            }
+        }
+    }
+
+    fn render_object(&mut self, position: Point, symbol: char) {
+        self.put(position, symbol, TERMINAL_FG, None);
+    }
+
+    fn render_colored_object(&mut self, position: Point, symbol: char, color: Color) {
+        self.put(position, symbol, color, Some(TERMINAL_BG));
+    }
+
+    // Redraws the whole frame in one write, only switching colours where they change.
+    fn after_render_new_frame(&mut self) {
+        let mut frame = String::from_str("\x1b[H");
+        for (y, row) in self.cells.iter().enumerate() {
+            let mut colors: Option<(Color, Color)> = None;
+            for cell in row.iter() {
+                if colors != Some((cell.fg, cell.bg)) {
+                    frame.push_str(format!("\x1b[38;2;{};{};{};48;2;{};{};{}m",
+                        cell.fg.r, cell.fg.g, cell.fg.b, cell.bg.r, cell.bg.g, cell.bg.b).as_slice());
+                    colors = Some((cell.fg, cell.bg));
+                }
+                frame.push(cell.symbol);
+            }
+            frame.push_str("\x1b[0m");
+            // Raw mode doesn't turn a newline into a carriage return as well.
+            if y + 1 < self.cells.len() {
+                frame.push_str("\r\n");
+            }
+        }
+        stdio::stdout_raw().write_str(frame.as_slice()).ok();
+    }
+
+    // A keypress is whatever a single read brings in, which keeps an arrow key's
+    // escape sequence together and tells it apart from Escape pressed on its own.
+    fn wait_for_keypress(&mut self) -> KeyboardInput {
+        let mut buffer = [0u8, ..8];
+        match stdio::stdin_raw().read(&mut buffer) {
+            Ok(count) => self.input_component.translate_input(buffer.slice_to(count).to_vec()),
+            // The terminal has gone away, so there's nobody left to play.
+            Err(_) => KeyboardInput { key: SpecialKey(KeyCode::Escape) }
+        }
+    }
+
+    fn attach_window(&mut self, window: &mut Box<WindowComponent>) {
+        let bounds = window.get_bounds();
+        let bg = window.get_bg_color();
+        for point in bounds.cells() {
+            self.put(point, ' ', TERMINAL_FG, Some(bg));
+        }
+        for (line, text) in window.visible_lines().iter().enumerate() {
+            for (column, symbol) in text.as_slice().chars().enumerate() {
+                let position = Point::new(bounds.min.x + column as i32, bounds.min.y + line as i32);
+                self.put(position, symbol, TERMINAL_FG, Some(bg));
+            }
+        }
+    }
+}
+
+impl Drop for TerminalRenderingComponent {
+    fn drop(&mut self) {
+        stdio::stdout_raw().write_str("\x1b[0m\x1b[?25h\x1b[2J\x1b[H").ok();
+        match self.saved_mode {
+            Some(ref mode) => { stty(&[mode.as_slice()]); },
+            None => { stty(&["sane"]); }
+        }
+    }
+}
+
+// What a headless backend has drawn: one character per cell, colours left out.
+pub struct Screen {
+    cells: Vec<Vec<char>>,
+    bounds: Bound
+}
 
-        for message in messages.iter() {
-            window.print(0, line, tcod::TextAlignment::Left, message.as_slice());
-            line = line + 1;
+impl Screen {
+    pub fn new(bounds: Bound) -> Screen {
+        Screen {
+            cells: Vec::from_elem(bounds.height() as uint, Vec::from_elem(bounds.width() as uint, ' ')),
+            bounds: bounds
         }
+    }
+
+    pub fn clear(&mut self) {
+        for row in self.cells.iter_mut() {
+            for cell in row.iter_mut() {
+                *cell = ' ';
+            }
+        }
+    }
+
+    // Anything off the screen is dropped, like a console would.
+    pub fn put(&mut self, position: Point, symbol: char) {
+        match self.bounds.grid_index(position) {
+            Some((x, y)) => self.cells[y][x] = symbol,
+            None => {}
+        }
+    }
+
+    pub fn print(&mut self, x: i32, y: i32, text: &str) {
+        for (offset, symbol) in text.chars().enumerate() {
+            self.put(Point::new(x + offset as i32, y), symbol);
+        }
+    }
+
+    pub fn char_at(&self, position: Point) -> Option<char> {
+        match self.bounds.grid_index(position) {
+            Some((x, y)) => Some(self.cells[y][x]),
+            None => None
+        }
+    }
+
+    pub fn row(&self, y: i32) -> String {
+        match self.bounds.grid_index(Point::new(self.bounds.min.x, y)) {
+            Some((_, y)) => self.cells[y].iter().map(|&symbol| symbol).collect(),
+            None => String::new()
+        }
+    }
+
+    pub fn contains(&self, text: &str) -> bool {
+        range(self.bounds.min.y, self.bounds.max.y + 1).any(|y| self.row(y).as_slice().contains(text))
+    }
+
+    // The whole screen, one line per row, for comparing runs or printing on failure.
+    pub fn dump(&self) -> String {
+        let rows: Vec<String> = range(self.bounds.min.y, self.bounds.max.y + 1).map(|y| self.row(y)).collect();
+        rows.connect("\n")
+    }
+}
+
+// Draws into a shared `Screen` instead of a window and takes its keys from a script,
+// so whole games can run in tests and simulations. Once the script runs out it presses
+// Escape, which ends the game.
+pub struct HeadlessRenderingComponent {
+    screen: Rc<RefCell<Screen>>,
+    script: Vec<KeyboardInput>
+}
+
+impl HeadlessRenderingComponent {
+    pub fn with_script(bound: Bound, keys: Vec<KeyboardInput>) -> HeadlessRenderingComponent {
+        let mut script = keys;
+        script.reverse();
+        HeadlessRenderingComponent { screen: Rc::new(RefCell::new(Screen::new(bound))), script: script }
+    }
 
-        let console = window.get_console();
+    // Keep hold of this before handing the component to a Game to look at what it drew.
+    pub fn screen(&self) -> Rc<RefCell<Screen>> {
+        self.screen.clone()
+    }
+}
 
-        Console::blit(&*console, 0, 0, (bounds.max.x as int) + 1,
-        (bounds.max.y as int) + 1, &mut self.console, bounds.min.x as int,
-        bounds.min.y as int, 1f32, 1f32);
+impl RenderingComponent for HeadlessRenderingComponent {
+    fn new(bound: Bound) -> HeadlessRenderingComponent {
+        HeadlessRenderingComponent::with_script(bound, vec![])
+    }
+
+    fn before_render_new_frame(&mut self) {
+        self.screen.borrow_mut().clear();
+    }
+
+    fn render_object(&mut self, position: Point, symbol: char) {
+        self.screen.borrow_mut().put(position, symbol);
+    }
+
+    fn render_colored_object(&mut self, position: Point, symbol: char, _: Color) {
+        self.screen.borrow_mut().put(position, symbol);
+    }
+
+    fn after_render_new_frame(&mut self) {}
+
+    fn wait_for_keypress(&mut self) -> KeyboardInput {
+        match self.script.pop() {
+            Some(key) => key,
+            None => KeyboardInput { key: SpecialKey(KeyCode::Escape) }
+        }
+    }
+
+    fn attach_window(&mut self, window: &mut Box<WindowComponent>) {
+        let bounds = window.get_bounds();
+        let mut screen = self.screen.borrow_mut();
+        for y in range(bounds.min.y, bounds.max.y + 1) {
+            screen.print(bounds.min.x, y, String::from_char(bounds.width() as uint, ' ').as_slice());
+        }
+        for (line, text) in window.visible_lines().iter().enumerate() {
+            screen.print(bounds.min.x, bounds.min.y + line as i32, text.as_slice());
+        }
     }
 }
@@ -0,0 +1,83 @@
+use std::io::File;
+use serialize::{ json, Decodable };
+
+use input::KeyboardInput;
+
+// Bump whenever anything below changes shape; older replays are refused rather than misplayed.
+pub static REPLAY_VERSION: u64 = 1;
+// Where the keys of the last game are kept, ready to go along with a bug report.
+pub static REPLAY_PATH: &'static str = "dwemthys.replay";
+
+// A game's seed and every key pressed in it. Fed back into a fresh game with the same
+// seed, the keys play it out exactly as it happened.
+#[deriving(Encodable, Decodable)]
+pub struct Replay {
+    pub version: u64,
+    pub seed: u32,
+    pub inputs: Vec<KeyboardInput>
+}
+
+impl Replay {
+    pub fn new(seed: u32) -> Replay {
+        Replay { version: REPLAY_VERSION, seed: seed, inputs: vec![] }
+    }
+
+    pub fn record(&mut self, input: KeyboardInput) {
+        self.inputs.push(input);
+    }
+
+    pub fn encode(&self) -> String {
+        json::encode(self)
+    }
+
+    pub fn decode(text: &str) -> Result<Replay, String> {
+        let json = try!(json::from_str(text).map_err(|e| e.to_string()));
+        match json.find("version").and_then(|version| version.as_u64()) {
+            Some(version) if version == REPLAY_VERSION => {},
+            Some(version) => return Err(format!("it was recorded by an incompatible version ({})", version)),
+            None => return Err("it isn't a replay".to_string())
+        }
+
+        let mut decoder = json::Decoder::new(json);
+        let replay: Result<Replay, json::DecoderError> = Decodable::decode(&mut decoder);
+        replay.map_err(|e| e.to_string())
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        File::create(path).write_str(self.encode().as_slice()).map_err(|e| e.to_string())
+    }
+
+    pub fn read(path: &Path) -> Result<Replay, String> {
+        let text = try!(File::open(path).read_to_string().map_err(|e| e.to_string()));
+        Replay::decode(text.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Replay;
+    use input::{ KeyboardInput, KeyCode };
+    use input::Key::{ Printable, SpecialKey };
+
+    #[test]
+    fn keys_come_back_in_order() {
+        let mut replay = Replay::new(99);
+        replay.record(KeyboardInput { key: SpecialKey(KeyCode::Left) });
+        replay.record(KeyboardInput { key: Printable('g') });
+
+        let loaded = Replay::decode(replay.encode().as_slice()).unwrap();
+        assert_eq!(loaded.seed, 99);
+        assert_eq!(loaded.inputs.len(), 2);
+        match (loaded.inputs[0].key, loaded.inputs[1].key) {
+            (SpecialKey(KeyCode::Left), Printable('g')) => {},
+            _ => panic!("the keys changed on the way through")
+        }
+    }
+
+    #[test]
+    fn replays_from_other_versions_are_refused() {
+        let mut replay = Replay::new(99);
+        replay.version += 1;
+        assert!(Replay::decode(replay.encode().as_slice()).is_err());
+    }
+}
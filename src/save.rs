@@ -0,0 +1,424 @@
+use std::cell::RefCell;
+use std::io::File;
+use std::io::fs;
+use std::rc::Rc;
+use serialize::{ json, Decodable };
+
+use util::{ Bound, Point };
+use util::Contains::{ DoesContain, DoesNotContain };
+use actor::Actor;
+use stats::Stats;
+use inventory::Inventory;
+use item::ItemKind;
+use terrain::{ Terrain, Tile };
+use mapgen::Level;
+use maps::{ Maps, Map, Corpse };
+use movement;
+use movement::{ MoveInfo, GameRng };
+use awareness::SavedAwareness;
+use dungeon::Dungeon;
+
+// Bump whenever anything below changes shape; older saves are refused rather than misread.
+pub static SAVE_VERSION: u64 = 3;
+pub static SAVE_PATH: &'static str = "dwemthys.sav";
+
+#[deriving(Encodable, Decodable)]
+pub struct SavedActor {
+    position: Point,
+    display_char: char,
+    name: String,
+    stats: Stats,
+    inventory: Inventory,
+    energy: i32,
+    earned: i32,
+    arrival: uint,
+    is_pc: bool,
+    // The movement component's registry name, see `movement::from_registry`.
+    movement: String,
+    awareness: Option<SavedAwareness>
+}
+
+#[deriving(Encodable, Decodable)]
+pub struct SavedLevel {
+    bounds: Bound,
+    // One tile per cell, in the order `Bound::cells` visits them.
+    tiles: Vec<Tile>,
+    explored: Vec<Point>,
+    items: Vec<(Point, ItemKind)>,
+    corpses: Vec<Corpse>,
+    up_stairs: Option<Point>,
+    down_stairs: Point,
+    pcs: Vec<SavedActor>,
+    friends: Vec<SavedActor>,
+    enemies: Vec<SavedActor>
+}
+
+// Everything needed to pick a game back up where it was left.
+#[deriving(Encodable, Decodable)]
+pub struct SavedGame {
+    pub version: u64,
+    pub seed: u32,
+    // How far the game's RNG had got, see `GameRng::resume`.
+    pub rng_seed: u32,
+    pub rng_draws: u64,
+    // See `MoveInfo::arrivals`.
+    pub arrivals: uint,
+    pub depth: uint,
+    pub bounds: Bound,
+    pub char_location: Point,
+    pub levels: Vec<SavedLevel>,
+    // The message log, newest first.
+    pub messages: Vec<String>,
+    // The screen the game was on, as named by `GameState::name`; see `ResumeGameState::resume`
+    // for why most of them aren't restored.
+    pub state: String
+}
+
+impl SavedGame {
+    pub fn new(dungeon: &Dungeon, move_info: Rc<RefCell<MoveInfo>>, messages: Vec<String>, state: &str) -> SavedGame {
+        let (char_location, rng_seed, rng_draws, arrivals) = {
+            let info = move_info.borrow();
+            (info.deref().char_location, info.deref().rng.seed(), info.deref().rng.draws(), info.deref().arrivals)
+        };
+        SavedGame {
+            version: SAVE_VERSION,
+            seed: dungeon.seed(),
+            rng_seed: rng_seed,
+            rng_draws: rng_draws,
+            arrivals: arrivals,
+            depth: dungeon.depth,
+            bounds: dungeon.bounds(),
+            char_location: char_location,
+            levels: dungeon.levels.iter().map(|maps| save_level(maps)).collect(),
+            messages: messages,
+            state: state.to_string()
+        }
+    }
+
+    // Rebuilds the dungeon around the shared move info, which is pointed back at the heroine.
+    pub fn restore<'a>(&self, move_info: Rc<RefCell<MoveInfo>>) -> Result<Dungeon<'a>, String> {
+        if self.depth < 1 || self.depth > self.levels.len() {
+            return Err(format!("depth {} is not one of its {} levels", self.depth, self.levels.len()));
+        }
+
+        {
+            let mut info = move_info.borrow_mut();
+            info.deref_mut().bounds = self.bounds;
+            info.deref_mut().char_location = self.char_location;
+        }
+
+        let mut levels = vec![];
+        for level in self.levels.iter() {
+            levels.push(try!(restore_level(level, move_info.clone())));
+        }
+        // Only now, since rebuilding some monsters rolls on the RNG and every actor counts as arriving.
+        {
+            let mut info = move_info.borrow_mut();
+            info.deref_mut().rng = GameRng::resume(self.rng_seed, self.rng_draws);
+            info.deref_mut().arrivals = self.arrivals;
+        }
+        Ok(Dungeon::restore(move_info, levels, self.depth, self.seed))
+    }
+
+    pub fn encode(&self) -> String {
+        json::encode(self)
+    }
+
+    pub fn decode(text: &str) -> Result<SavedGame, String> {
+        let json = try!(json::from_str(text).map_err(|e| e.to_string()));
+        match json.find("version").and_then(|version| version.as_u64()) {
+            Some(version) if version == SAVE_VERSION => {},
+            Some(version) => return Err(format!("it was saved by an incompatible version ({})", version)),
+            None => return Err("it isn't a save file".to_string())
+        }
+
+        let mut decoder = json::Decoder::new(json);
+        let saved: Result<SavedGame, json::DecoderError> = Decodable::decode(&mut decoder);
+        saved.map_err(|e| e.to_string())
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        File::create(path).write_str(self.encode().as_slice()).map_err(|e| e.to_string())
+    }
+
+    pub fn read(path: &Path) -> Result<SavedGame, String> {
+        let text = try!(File::open(path).read_to_string().map_err(|e| e.to_string()));
+        SavedGame::decode(text.as_slice())
+    }
+}
+
+// Where the game is kept between launches. Tests hand the game one of their own so
+// they never touch the player's save.
+#[deriving(Clone)]
+pub struct Store {
+    path: Path
+}
+
+impl Store {
+    pub fn new(path: Path) -> Store {
+        Store { path: path }
+    }
+
+    // The save in the directory the game was started from.
+    pub fn default() -> Store {
+        Store::new(Path::new(SAVE_PATH))
+    }
+
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    pub fn delete(&self) {
+        fs::unlink(&self.path).ok();
+    }
+
+    pub fn read(&self) -> Result<SavedGame, String> {
+        SavedGame::read(&self.path)
+    }
+
+    pub fn write(&self, saved: &SavedGame) -> Result<(), String> {
+        saved.write(&self.path)
+    }
+}
+
+fn save_level(maps: &Maps) -> SavedLevel {
+    let bounds = maps.terrain.bounds();
+    let mut tiles = vec![];
+    let mut explored = vec![];
+    let mut items = vec![];
+    for point in bounds.cells() {
+        tiles.push(maps.terrain.get(point).unwrap());
+        if maps.explored.is_explored(point) {
+            explored.push(point);
+        }
+        for &kind in maps.items.items_at(point).iter() {
+            items.push((point, kind));
+        }
+    }
+
+    SavedLevel {
+        bounds: bounds,
+        tiles: tiles,
+        explored: explored,
+        items: items,
+        corpses: maps.corpses.iter().map(|corpse| Corpse { position: corpse.position, name: corpse.name.clone() }).collect(),
+        up_stairs: maps.up_stairs,
+        down_stairs: maps.down_stairs,
+        pcs: save_layer(&*maps.pcs),
+        friends: save_layer(&*maps.friends),
+        enemies: save_layer(&*maps.enemies)
+    }
+}
+
+fn restore_level<'a>(saved: &SavedLevel, move_info: Rc<RefCell<MoveInfo>>) -> Result<Maps<'a>, String> {
+    if saved.tiles.len() != saved.bounds.cells().count() {
+        return Err("a level's tiles don't fill its bounds".to_string());
+    }
+    // Anything placed outside the level would be lost at best and crash the game at worst.
+    let mut places = vec![("the stairs down", saved.down_stairs)];
+    match saved.up_stairs {
+        Some(point) => places.push(("the stairs up", point)),
+        None => {}
+    }
+    for &(point, kind) in saved.items.iter() {
+        places.push((kind.name(), point));
+    }
+    for actor in saved.pcs.iter().chain(saved.friends.iter()).chain(saved.enemies.iter()) {
+        places.push((actor.name.as_slice(), actor.position));
+    }
+    for &(what, point) in places.iter() {
+        match saved.bounds.contains(point) {
+            DoesContain => {},
+            DoesNotContain => return Err(format!("{} at ({}, {}) is outside its level", what, point.x, point.y))
+        }
+    }
+
+    let mut terrain = Terrain::filled(saved.bounds, Tile::Floor);
+    for (point, &tile) in saved.bounds.cells().zip(saved.tiles.iter()) {
+        terrain.set(point, tile);
+    }
+
+    // The stairs are already among the tiles and nothing new should spawn.
+    let level = Level {
+        terrain: terrain,
+        start: saved.down_stairs,
+        exit: saved.down_stairs,
+        spawns: vec![],
+        items: saved.items.iter().map(|&item| item).collect()
+    };
+    let mut maps = Maps::new(move_info.clone(), level, saved.up_stairs);
+    for &point in saved.explored.iter() {
+        maps.explored.mark(point);
+    }
+    for corpse in saved.corpses.iter() {
+        maps.corpses.push(Corpse { position: corpse.position, name: corpse.name.clone() });
+    }
+
+    try!(restore_layer(&mut *maps.pcs, &saved.pcs, move_info.clone()));
+    try!(restore_layer(&mut *maps.friends, &saved.friends, move_info.clone()));
+    try!(restore_layer(&mut *maps.enemies, &saved.enemies, move_info.clone()));
+    Ok(maps)
+}
+
+fn save_layer(map: &Map) -> Vec<SavedActor> {
+    map.ids().iter().map(|&id| save_actor(&**map.get(id).unwrap())).collect()
+}
+
+fn restore_layer(map: &mut Map, saved: &Vec<SavedActor>, move_info: Rc<RefCell<MoveInfo>>) -> Result<(), String> {
+    for actor in saved.iter() {
+        let arrival = actor.arrival;
+        let actor = try!(restore_actor(actor, move_info.clone()));
+        let id = map.push_actor(actor.position, actor);
+        map.get_mut(id).unwrap().arrival = arrival;
+    }
+    Ok(())
+}
+
+fn save_actor(actor: &Actor) -> SavedActor {
+    SavedActor {
+        position: actor.position,
+        display_char: actor.display_char,
+        name: actor.name.clone(),
+        stats: actor.stats,
+        inventory: actor.inventory.clone(),
+        energy: actor.energy,
+        earned: actor.earned,
+        arrival: actor.arrival,
+        is_pc: actor.is_pc,
+        movement: actor.movement_name().to_string(),
+        awareness: actor.awareness().map(|awareness| awareness.save())
+    }
+}
+
+fn restore_actor(saved: &SavedActor, move_info: Rc<RefCell<MoveInfo>>) -> Result<Box<Actor>, String> {
+    let mc = match movement::from_registry(saved.movement.as_slice(), move_info) {
+        Some(mc) => mc,
+        None => return Err(format!("the {} moves in an unknown way ({})", saved.name, saved.movement))
+    };
+    let position = saved.position;
+    let mut actor = box Actor::new(position.x, position.y, saved.display_char, saved.name.as_slice(), saved.stats, mc, saved.is_pc);
+    actor.inventory = saved.inventory.clone();
+    actor.energy = saved.energy;
+    actor.earned = saved.earned;
+    match (actor.awareness(), &saved.awareness) {
+        (Some(awareness), &Some(ref snapshot)) => awareness.restore(snapshot),
+        _ => {}
+    }
+    Ok(actor)
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rand::Rng;
+    use std::rc::Rc;
+
+    use super::SavedGame;
+    use actor::Actor;
+    use awareness::{ State, SavedAwareness };
+    use dungeon::Dungeon;
+    use item::ItemKind;
+    use mapgen::Level;
+    use movement::MoveInfo;
+    use terrain::{ Terrain, Tile };
+    use util::{ Bound, Point };
+
+    fn small_dungeon(move_info: Rc<RefCell<MoveInfo>>) -> Dungeon<'static> {
+        let bounds = Bound::new(0, 0, 19, 9);
+        let level = Level {
+            terrain: Terrain::new(bounds),
+            start: Point::new(2, 2),
+            exit: Point::new(17, 7),
+            spawns: vec![],
+            items: vec![(Point::new(4, 4), ItemKind::Boomerang)]
+        };
+        Dungeon::new(move_info, level, 1234)
+    }
+
+    fn move_info() -> Rc<RefCell<MoveInfo>> {
+        Rc::new(RefCell::new(MoveInfo::new(Bound::new(0, 0, 19, 9), Point::new(2, 2))))
+    }
+
+    fn rolls(move_info: Rc<RefCell<MoveInfo>>, count: uint) -> Vec<u32> {
+        let mut info = move_info.borrow_mut();
+        range(0, count).map(|_| info.deref_mut().rng.gen()).collect()
+    }
+
+    #[test]
+    fn a_saved_game_comes_back_the_same() {
+        let info = move_info();
+        let mut dungeon = small_dungeon(info.clone());
+        {
+            let maps = dungeon.current();
+            maps.terrain.set(Point::new(8, 3), Tile::Water);
+            maps.explored.mark(Point::new(5, 5));
+            let jackal = box Actor::jackal(9, 6, info.clone());
+            jackal.awareness().unwrap().restore(&SavedAwareness {
+                state: State::Searching,
+                last_known: Some(Point::new(3, 3)),
+                search_turns: 4
+            });
+            maps.enemies.push_actor(Point::new(9, 6), jackal);
+        }
+
+        let saved = SavedGame::new(&dungeon, info.clone(), vec!["Hello.".to_string()], "movement");
+        let text = saved.encode();
+        let loaded = SavedGame::decode(text.as_slice()).unwrap();
+        assert_eq!(loaded.messages, vec!["Hello.".to_string()]);
+
+        let mut restored = loaded.restore(move_info()).unwrap();
+        assert_eq!(restored.depth, 1);
+        let maps = restored.current();
+        assert!(maps.terrain.get(Point::new(8, 3)) == Some(Tile::Water));
+        assert!(maps.terrain.get(Point::new(17, 7)) == Some(Tile::StairsDown));
+        assert!(maps.explored.is_explored(Point::new(5, 5)));
+        assert!(maps.items.items_at(Point::new(4, 4)) == [ItemKind::Boomerang].as_slice());
+
+        let heroine = maps.heroine().unwrap();
+        assert!(heroine.inventory.contains(ItemKind::HeroicSword));
+        assert_eq!(heroine.movement_name(), "user");
+
+        let jackal = maps.enemies.actor_at(Point::new(9, 6)).unwrap();
+        assert_eq!(jackal.movement_name(), "hunter");
+        let awareness = jackal.awareness().unwrap();
+        assert_eq!(awareness.state(), State::Searching);
+        let target = awareness.target().unwrap();
+        assert_eq!((target.x, target.y), (3, 3));
+    }
+
+    #[test]
+    fn saves_from_other_versions_are_refused() {
+        let info = move_info();
+        let dungeon = small_dungeon(info.clone());
+        let mut saved = SavedGame::new(&dungeon, info, vec![], "movement");
+        saved.version += 1;
+        assert!(SavedGame::decode(saved.encode().as_slice()).is_err());
+    }
+
+    #[test]
+    fn items_outside_their_level_are_refused() {
+        let info = move_info();
+        let dungeon = small_dungeon(info.clone());
+        let mut saved = SavedGame::new(&dungeon, info, vec![], "movement");
+        saved.levels[0].items.push((Point::new(25, 3), ItemKind::Boomerang));
+        assert!(saved.restore(move_info()).is_err());
+    }
+
+    #[test]
+    fn a_resumed_game_rolls_like_one_that_was_never_saved() {
+        let saved_info = Rc::new(RefCell::new(MoveInfo::with_seed(Bound::new(0, 0, 19, 9), Point::new(2, 2), 77)));
+        let unsaved_info = Rc::new(RefCell::new(MoveInfo::with_seed(Bound::new(0, 0, 19, 9), Point::new(2, 2), 77)));
+        let mut saved_dungeon = small_dungeon(saved_info.clone());
+        let mut unsaved_dungeon = small_dungeon(unsaved_info.clone());
+        // Kobolds roll on the RNG as they are built, and again as they are restored.
+        saved_dungeon.current().enemies.push_actor(Point::new(12, 6), box Actor::kobold(12, 6, saved_info.clone()));
+        unsaved_dungeon.current().enemies.push_actor(Point::new(12, 6), box Actor::kobold(12, 6, unsaved_info.clone()));
+        assert_eq!(rolls(saved_info.clone(), 5), rolls(unsaved_info.clone(), 5));
+
+        let saved = SavedGame::new(&saved_dungeon, saved_info, vec![], "movement");
+        let resumed_info = move_info();
+        SavedGame::decode(saved.encode().as_slice()).unwrap().restore(resumed_info.clone()).unwrap();
+
+        assert_eq!(rolls(resumed_info, 20), rolls(unsaved_info, 20));
+    }
+}
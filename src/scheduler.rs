@@ -0,0 +1,165 @@
+use maps::{ Maps, Layer, ActorId, LAYERS };
+
+// Every action costs the same; speed only changes how quickly an actor earns it back.
+// At speed 100 an actor acts once per tick, at 200 twice and at 50 every other tick.
+pub static ACTION_COST: i32 = 100;
+
+// The actor with the most energy to spare, as long as it has enough to act. Ties go to
+// whoever has built up more energy on the level, so the faster actor, and then to
+// whoever arrived first, whatever layer they are on.
+pub fn next_ready(maps: &Maps) -> Option<(Layer, ActorId)> {
+    let mut ready: Option<(Layer, ActorId, (i32, i32, uint))> = None;
+    for &layer in LAYERS.iter() {
+        for id in maps.layer(layer).ids().into_iter() {
+            let actor = maps.layer(layer).get(id).unwrap();
+            if actor.energy < ACTION_COST {
+                continue;
+            }
+            let rank = (actor.energy, actor.earned, actor.arrival);
+            match ready {
+                Some((_, _, best)) if !goes_before(rank, best) => {},
+                _ => ready = Some((layer, id, rank))
+            }
+        }
+    }
+    ready.map(|(layer, id, _)| (layer, id))
+}
+
+fn goes_before(rank: (i32, i32, uint), other: (i32, i32, uint)) -> bool {
+    let (energy, earned, arrival) = rank;
+    let (other_energy, other_earned, other_arrival) = other;
+    if energy != other_energy {
+        energy > other_energy
+    } else if earned != other_earned {
+        earned > other_earned
+    } else {
+        arrival < other_arrival
+    }
+}
+
+// Lets one unit of time pass, giving every actor energy in proportion to its speed.
+pub fn tick(maps: &mut Maps) {
+    for &layer in LAYERS.iter() {
+        let map = maps.layer_mut(layer);
+        for id in map.ids().into_iter() {
+            let actor = map.get_mut(id).unwrap();
+            let speed = actor.effective_stats().speed;
+            actor.energy += speed;
+            actor.earned += speed;
+        }
+    }
+}
+
+pub fn spend(maps: &mut Maps, layer: Layer, id: ActorId) {
+    match maps.layer_mut(layer).get_mut(id) {
+        Some(actor) => actor.energy -= ACTION_COST,
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::{ next_ready, tick, spend };
+    use actor::Actor;
+    use maps::{ Maps, Layer };
+    use mapgen::Level;
+    use movement::MoveInfo;
+    use terrain::Terrain;
+    use util::{ Bound, Point };
+
+    fn empty_maps() -> Maps<'static> {
+        let bounds = Bound::new(0, 0, 19, 9);
+        let move_info = Rc::new(RefCell::new(MoveInfo::new(bounds, Point::new(1, 1))));
+        let level = Level {
+            terrain: Terrain::new(bounds),
+            start: Point::new(1, 1),
+            exit: Point::new(18, 8),
+            spawns: vec![],
+            items: vec![]
+        };
+        Maps::new(move_info, level, None)
+    }
+
+    // Counts how often an actor of the given speed gets to act over `ticks` ticks.
+    fn actions_over(speed: i32, ticks: uint) -> uint {
+        let mut maps = empty_maps();
+        let move_info = Rc::new(RefCell::new(MoveInfo::new(Bound::new(0, 0, 19, 9), Point::new(1, 1))));
+        let mut actor = box Actor::kobold(5, 5, move_info);
+        actor.stats.speed = speed;
+        let id = maps.enemies.push_actor(Point::new(5, 5), actor);
+
+        let mut actions = 0u;
+        for _ in range(0, ticks) {
+            tick(&mut maps);
+            while next_ready(&maps).is_some() {
+                spend(&mut maps, Layer::Enemies, id);
+                actions += 1;
+            }
+        }
+        actions
+    }
+
+    #[test]
+    fn normal_speed_acts_once_per_tick() {
+        assert_eq!(actions_over(100, 10), 10);
+    }
+
+    #[test]
+    fn double_speed_acts_twice_per_tick() {
+        assert_eq!(actions_over(200, 10), 20);
+    }
+
+    #[test]
+    fn half_speed_acts_every_other_tick() {
+        assert_eq!(actions_over(50, 10), 5);
+    }
+
+    #[test]
+    fn most_energy_acts_first() {
+        let mut maps = empty_maps();
+        let move_info = Rc::new(RefCell::new(MoveInfo::new(Bound::new(0, 0, 19, 9), Point::new(1, 1))));
+        let slow = maps.enemies.push_actor(Point::new(3, 3), box Actor::kobold(3, 3, move_info.clone()));
+        let fast = maps.friends.push_actor(Point::new(4, 4), box Actor::cat(4, 4, move_info.clone()));
+
+        tick(&mut maps);
+        match next_ready(&maps) {
+            Some((Layer::Friends, id)) => assert_eq!(id, fast),
+            _ => panic!("the cat should act first")
+        }
+        spend(&mut maps, Layer::Friends, fast);
+        match next_ready(&maps) {
+            Some((Layer::Enemies, id)) => assert_eq!(id, slow),
+            _ => panic!("the kobold should act next")
+        }
+    }
+
+    #[test]
+    fn double_speed_acts_twice_per_heroine_action_from_the_first_turn() {
+        let mut maps = empty_maps();
+        let move_info = Rc::new(RefCell::new(MoveInfo::new(Bound::new(0, 0, 19, 9), Point::new(1, 1))));
+        let mut fast = box Actor::kobold(5, 5, move_info.clone());
+        fast.stats.speed = 200;
+        maps.enemies.push_actor(Point::new(5, 5), fast);
+        let heroine = maps.pcs.push_actor(Point::new(1, 1), box Actor::heroine(move_info.clone()));
+
+        // The same loop `Maps::end_turn` runs after each of her actions.
+        for _ in range(0u, 5) {
+            spend(&mut maps, Layer::Pcs, heroine);
+            let mut actions = 0u;
+            loop {
+                match next_ready(&maps) {
+                    Some((Layer::Pcs, _)) => break,
+                    Some((layer, id)) => {
+                        spend(&mut maps, layer, id);
+                        actions += 1;
+                    },
+                    None => tick(&mut maps)
+                }
+            }
+            assert_eq!(actions, 2);
+        }
+    }
+}
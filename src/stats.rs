@@ -0,0 +1,61 @@
+use std::cmp::{ max, min };
+
+// Speed is measured against a normal speed of 100: 200 is twice as fast.
+#[deriving(Encodable, Decodable)]
+pub struct Stats {
+    pub hp: i32,
+    pub max_hp: i32,
+    pub attack: i32,
+    pub defense: i32,
+    pub speed: i32
+}
+
+// What a piece of equipment adds to its wearer's stats while it is equipped.
+pub struct Bonus {
+    pub attack: i32,
+    pub defense: i32,
+    pub speed: i32
+}
+
+impl Bonus {
+    pub fn new(attack: i32, defense: i32, speed: i32) -> Bonus {
+        Bonus { attack: attack, defense: defense, speed: speed }
+    }
+
+    pub fn none() -> Bonus {
+        Bonus::new(0, 0, 0)
+    }
+
+    pub fn add(&self, other: &Bonus) -> Bonus {
+        Bonus::new(self.attack + other.attack, self.defense + other.defense, self.speed + other.speed)
+    }
+}
+
+impl Stats {
+    pub fn new(max_hp: i32, attack: i32, defense: i32, speed: i32) -> Stats {
+        Stats { hp: max_hp, max_hp: max_hp, attack: attack, defense: defense, speed: speed }
+    }
+
+    pub fn with_bonus(&self, bonus: &Bonus) -> Stats {
+        Stats {
+            hp: self.hp,
+            max_hp: self.max_hp,
+            attack: self.attack + bonus.attack,
+            defense: self.defense + bonus.defense,
+            // Nothing can slow an actor down to a standstill.
+            speed: max(10, self.speed + bonus.speed)
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.hp <= 0
+    }
+
+    pub fn take_damage(&mut self, amount: i32) {
+        self.hp = self.hp - max(0, amount);
+    }
+
+    pub fn heal(&mut self, amount: i32) {
+        self.hp = min(self.max_hp, self.hp + max(0, amount));
+    }
+}
@@ -0,0 +1,156 @@
+use util::{ Bound, Point };
+use color::Color;
+use rendering::RenderingComponent;
+use fov::{ Fov, Explored };
+use camera::Camera;
+
+#[deriving(Clone, PartialEq, Encodable, Decodable)]
+pub enum Tile {
+    Floor,
+    Wall,
+    Door,
+    Water,
+    StairsUp,
+    StairsDown
+}
+
+pub struct TileInfo {
+    pub display_char: char,
+    pub color: Color,
+    pub is_passable: bool,
+    pub blocks_sight: bool
+}
+
+impl Tile {
+    pub fn info(&self) -> TileInfo {
+        match *self {
+            Tile::Floor => TileInfo {
+                display_char: '.',
+                color: Color::new(90u8, 90u8, 90u8),
+                is_passable: true,
+                blocks_sight: false
+            },
+            Tile::Wall => TileInfo {
+                display_char: '#',
+                color: Color::new(160u8, 130u8, 90u8),
+                is_passable: false,
+                blocks_sight: true
+            },
+            Tile::Door => TileInfo {
+                display_char: '+',
+                color: Color::new(200u8, 140u8, 40u8),
+                is_passable: true,
+                blocks_sight: true
+            },
+            Tile::Water => TileInfo {
+                display_char: '~',
+                color: Color::new(40u8, 90u8, 220u8),
+                is_passable: false,
+                blocks_sight: false
+            },
+            Tile::StairsUp => TileInfo {
+                display_char: '<',
+                color: Color::new(255u8, 255u8, 255u8),
+                is_passable: true,
+                blocks_sight: false
+            },
+            Tile::StairsDown => TileInfo {
+                display_char: '>',
+                color: Color::new(255u8, 255u8, 255u8),
+                is_passable: true,
+                blocks_sight: false
+            }
+        }
+    }
+
+    pub fn is_passable(&self) -> bool {
+        self.info().is_passable
+    }
+
+    pub fn blocks_sight(&self) -> bool {
+        self.info().blocks_sight
+    }
+}
+
+pub struct Terrain {
+    tiles: Vec<Vec<Tile>>,
+    size: Bound,
+    // Bumped on every change so anything derived from the tiles knows when to redo it.
+    revision: uint
+}
+
+impl Terrain {
+    pub fn new(size: Bound) -> Terrain {
+        let mut terrain = Terrain::filled(size, Tile::Floor);
+        for point in size.cells() {
+            if point.x == size.min.x || point.x == size.max.x || point.y == size.min.y || point.y == size.max.y {
+                terrain.set(point, Tile::Wall);
+            }
+        }
+
+        terrain
+    }
+
+    pub fn filled(size: Bound, tile: Tile) -> Terrain {
+        let tiles = Vec::from_elem(size.width() as uint, Vec::from_elem(size.height() as uint, tile));
+        Terrain { tiles: tiles, size: size, revision: 0 }
+    }
+
+    pub fn bounds(&self) -> Bound {
+        self.size
+    }
+
+    pub fn revision(&self) -> uint {
+        self.revision
+    }
+
+    pub fn get(&self, point: Point) -> Option<Tile> {
+        match self.size.grid_index(point) {
+            Some((x, y)) => Some(self.tiles[x][y]),
+            None => None
+        }
+    }
+
+    pub fn set(&mut self, point: Point, tile: Tile) {
+        match self.size.grid_index(point) {
+            Some((x, y)) => {
+                self.tiles[x][y] = tile;
+                self.revision += 1;
+            },
+            None => {}
+        }
+    }
+
+    pub fn is_passable(&self, point: Point) -> bool {
+        match self.get(point) {
+            Some(tile) => tile.is_passable(),
+            None => false
+        }
+    }
+
+    pub fn blocks_sight(&self, point: Point) -> bool {
+        match self.get(point) {
+            Some(tile) => tile.blocks_sight(),
+            None => true
+        }
+    }
+
+    pub fn render(&self, renderer: &mut Box<RenderingComponent>, camera: &Camera, fov: &Fov, explored: &Explored) {
+        for screen in camera.viewport.cells() {
+            let point = camera.to_world(screen);
+            let info = match self.get(point) {
+                Some(tile) => tile.info(),
+                None => continue
+            };
+            if fov.is_visible(point) {
+                renderer.render_colored_object(screen, info.display_char, info.color);
+            } else if explored.is_explored(point) {
+                renderer.render_colored_object(screen, info.display_char, dim(info.color));
+            }
+        }
+    }
+}
+
+fn dim(color: Color) -> Color {
+    Color::new(color.r / 3, color.g / 3, color.b / 3)
+}
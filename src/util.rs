@@ -3,6 +3,7 @@ use self::XPointRelation::{ RightOfPoint, LeftOfPoint, OnPointX };
 use self::YPointRelation::{ AbovePoint, BelowPoint, OnPointY };
 use self::PointRelation::{ PointsEqual, PointsNotEqual };
 
+#[deriving(Encodable, Decodable)]
 pub struct Point {
     pub x: i32,
     pub y: i32
@@ -76,11 +77,20 @@ pub enum Contains {
     DoesNotContain
 }
 
+// Both corners of a Bound are inclusive, so Bound::new(0, 0, 78, 49) covers
+// 79 columns and 50 rows. Grids backing a Bound are `width` by `height` and
+// indexed relative to `min` through `grid_index`.
+#[deriving(Encodable, Decodable)]
 pub struct Bound {
     pub min: Point,
     pub max: Point
 }
 
+pub struct Cells {
+    bound: Bound,
+    next: Option<Point>
+}
+
 impl Bound {
     pub fn new(min_x: i32, min_y: i32, max_x: i32, max_y: i32) -> Bound {
         Bound {
@@ -89,6 +99,18 @@ impl Bound {
         }
     }
 
+    pub fn width(&self) -> i32 {
+        self.max.x - self.min.x + 1
+    }
+
+    pub fn height(&self) -> i32 {
+        self.max.y - self.min.y + 1
+    }
+
+    pub fn area(&self) -> uint {
+        (self.width() * self.height()) as uint
+    }
+
     pub fn contains(&self, point: Point) -> Contains {
         if
             point.x >= self.min.x &&
@@ -101,4 +123,70 @@ impl Bound {
             DoesNotContain
         }
     }
+
+    pub fn grid_index(&self, point: Point) -> Option<(uint, uint)> {
+        match self.contains(point) {
+            DoesContain => Some(((point.x - self.min.x) as uint, (point.y - self.min.y) as uint)),
+            DoesNotContain => None
+        }
+    }
+
+    pub fn cells(&self) -> Cells {
+        let next = if self.width() > 0 && self.height() > 0 { Some(self.min) } else { None };
+        Cells { bound: *self, next: next }
+    }
+}
+
+impl Iterator<Point> for Cells {
+    fn next(&mut self) -> Option<Point> {
+        let current = self.next;
+        self.next = match current {
+            Some(point) if point.y < self.bound.max.y => Some(point.offset_y(1)),
+            Some(point) if point.x < self.bound.max.x => Some(Point::new(point.x + 1, self.bound.min.y)),
+            _ => None
+        };
+        current
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ Bound, Point };
+    use super::Contains::{ DoesContain, DoesNotContain };
+
+    #[test]
+    fn corners_are_inclusive() {
+        let bound = Bound::new(0, 0, 78, 49);
+        assert_eq!(bound.width(), 79);
+        assert_eq!(bound.height(), 50);
+        match bound.contains(Point::new(78, 49)) {
+            DoesContain => {},
+            DoesNotContain => panic!("max corner should be inside")
+        }
+        match bound.contains(Point::new(79, 49)) {
+            DoesContain => panic!("past the max corner should be outside"),
+            DoesNotContain => {}
+        }
+    }
+
+    #[test]
+    fn grid_index_is_relative_to_min() {
+        let bound = Bound::new(3, 4, 6, 8);
+        assert_eq!(bound.grid_index(Point::new(3, 4)), Some((0, 0)));
+        assert_eq!(bound.grid_index(Point::new(6, 8)), Some((3, 4)));
+        assert_eq!(bound.grid_index(Point::new(7, 8)), None);
+        assert_eq!(bound.grid_index(Point::new(2, 4)), None);
+    }
+
+    #[test]
+    fn cells_visits_every_point_once() {
+        let bound = Bound::new(1, 2, 4, 3);
+        let cells: Vec<Point> = bound.cells().collect();
+        assert_eq!(cells.len(), bound.area());
+        assert_eq!((cells[0].x, cells[0].y), (1, 2));
+        assert_eq!((cells[1].x, cells[1].y), (1, 3));
+        assert_eq!((cells[2].x, cells[2].y), (2, 2));
+        let last = cells[cells.len() - 1];
+        assert_eq!((last.x, last.y), (4, 3));
+    }
 }
@@ -1,34 +1,23 @@
-extern crate tcod;
-use self::tcod::{Console, KeyState, BackgroundFlag, TextAlignment, Color};
-
 use util::Bound;
-use input::InputComponent;
-
-pub struct TcodRenderingComponent<'a> {
-    pub console: Console,
-    pub input_component: Box<InputComponent<KeyState> + 'a>
-}
+use color::Color;
 
+// A window only keeps its text; rendering components draw it inside its bounds.
 pub trait WindowComponent {
     fn new(Bound) -> Self;
 
     fn get_bounds(&self) -> Bound;
     fn get_bg_color(&self) -> Color;
-    fn get_console(&mut self) -> &mut Console;
     fn get_mut_messages(&mut self) -> &mut Vec<Box<String>>;
     fn get_messages(&self) -> Vec<Box<String>>;
     fn get_max_messages(&self) -> uint;
 
-    fn clear(&mut self) {
-        let color = self.get_bg_color();
-        let console = self.get_console();
-        console.set_default_background(color);
-        console.clear();
-    }
-
-    fn print(&mut self, x: int, y: int, alignment: tcod::TextAlignment, text: &str) {
-        let mut console = self.get_console();
-        console.print_ex(x, y, BackgroundFlag::Set, alignment, text);
+    // The lines that fit inside the window, top to bottom, cut off at its right edge.
+    fn visible_lines(&self) -> Vec<String> {
+        let bounds = self.get_bounds();
+        self.get_messages().iter()
+            .take(bounds.height() as uint)
+            .map(|message| message.as_slice().chars().take(bounds.width() as uint).collect())
+            .collect()
     }
 
     fn buffer_message(&mut self, text: &str) {
@@ -40,6 +29,14 @@ pub trait WindowComponent {
         messages.truncate(max);
     }
 
+    // Replaces the contents with `lines`, shown top to bottom.
+    fn set_lines(&mut self, lines: &[String]) {
+        self.flush_buffer();
+        for line in lines.iter().rev() {
+            self.buffer_message(line.as_slice());
+        }
+    }
+
     fn flush_buffer(&mut self) {
         let max = self.get_max_messages();
         let messages = self.get_mut_messages();
@@ -56,7 +53,7 @@ impl WindowComponent for TcodStatsWindowComponent {
     window_component_init!(
         TcodStatsWindowComponent,
         Color::new(0u8, 0u8, 0u8),
-        10u
+        20u
     )
     window_component_getters!()
 }
@@ -91,3 +88,13 @@ impl WindowComponent for TcodMessagesWindowComponent {
     window_component_getters!()
 }
 
+
+window_component_def!(TcodInventoryWindowComponent)
+impl WindowComponent for TcodInventoryWindowComponent {
+    window_component_init!(
+        TcodInventoryWindowComponent,
+        Color::new(20u8, 20u8, 40u8),
+        14u
+    )
+    window_component_getters!()
+}
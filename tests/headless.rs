@@ -0,0 +1,112 @@
+extern crate dwemthys;
+
+use std::cell::RefCell;
+use std::io::TempDir;
+use std::rc::Rc;
+
+use dwemthys::game::{ Game, SCREEN_BOUNDS };
+use dwemthys::input::{ KeyboardInput, KeyCode };
+use dwemthys::input::Key::{ Printable, SpecialKey };
+use dwemthys::rendering::{ RenderingComponent, HeadlessRenderingComponent, Screen };
+use dwemthys::save::Store;
+
+fn key(c: char) -> KeyboardInput {
+    KeyboardInput { key: Printable(c) }
+}
+
+fn special(code: KeyCode) -> KeyboardInput {
+    KeyboardInput { key: SpecialKey(code) }
+}
+
+// Somewhere empty to save to, well away from the player's own save.
+fn scratch_store() -> (Store, TempDir) {
+    let dir = TempDir::new("dwemthys").unwrap();
+    (Store::new(dir.path().join("game.sav")), dir)
+}
+
+// Plays `keys` through a fresh game, the same way `main` drives it, and hands back
+// the game along with what ended up on screen.
+fn play(seed: u32, keys: Vec<KeyboardInput>) -> (Game<'static>, Rc<RefCell<Screen>>) {
+    let count = keys.len();
+    let rc = box HeadlessRenderingComponent::with_script(SCREEN_BOUNDS, keys);
+    let screen = rc.screen();
+    let (store, _dir) = scratch_store();
+    let mut game = Game::with_seed(rc as Box<RenderingComponent>, seed, store);
+
+    game.render();
+    for _ in range(0, count) {
+        if game.exit {
+            break;
+        }
+        game.wait_for_keypress();
+        game.update();
+        game.render();
+    }
+    (game, screen)
+}
+
+#[test]
+fn the_stats_window_shows_the_seed_and_depth() {
+    let (_, screen) = play(7, vec![]);
+    let screen = screen.borrow();
+    assert!(screen.contains("Seed: 7"), "{}", screen.dump());
+    assert!(screen.contains("Depth: 1"), "{}", screen.dump());
+    assert!(screen.contains("@"), "{}", screen.dump());
+}
+
+#[test]
+fn the_inventory_lists_what_she_starts_with() {
+    let (_, screen) = play(7, vec![key('i')]);
+    let screen = screen.borrow();
+    assert!(screen.contains("Inventory (2/10)"), "{}", screen.dump());
+    assert!(screen.contains("a) / Heroic Sword (equipped)"), "{}", screen.dump());
+    assert!(screen.contains("b) % Delicious Lettuce"), "{}", screen.dump());
+}
+
+#[test]
+fn escape_quits() {
+    let (game, _) = play(7, vec![special(KeyCode::Escape)]);
+    assert!(game.exit);
+}
+
+#[test]
+fn running_out_of_script_quits() {
+    let rc = box HeadlessRenderingComponent::with_script(SCREEN_BOUNDS, vec![]);
+    let (store, _dir) = scratch_store();
+    let mut game = Game::with_seed(rc as Box<RenderingComponent>, 7, store);
+    game.wait_for_keypress();
+    game.update();
+    assert!(game.exit);
+}
+
+#[test]
+fn the_same_seed_and_keys_play_out_the_same() {
+    let keys = || vec![
+        special(KeyCode::Left), special(KeyCode::Left), special(KeyCode::Up),
+        key('.'), key('.'), key('.'), key('.'),
+        special(KeyCode::Right), special(KeyCode::Down), special(KeyCode::Down)
+    ];
+    let (_, first) = play(1234, keys());
+    let (_, second) = play(1234, keys());
+    assert_eq!(first.borrow().dump(), second.borrow().dump());
+}
+
+#[test]
+fn without_a_save_the_game_starts_on_the_map() {
+    let rc = box HeadlessRenderingComponent::with_script(SCREEN_BOUNDS, vec![]);
+    let (store, _dir) = scratch_store();
+    let game = Game::new(rc as Box<RenderingComponent>, Some(7), store);
+    assert_eq!(game.game_state.name(), "movement");
+}
+
+#[test]
+fn a_save_in_the_store_is_offered_for_resuming() {
+    let (store, _dir) = scratch_store();
+    let (mut first, _) = play(7, vec![key('.')]);
+    first.store = store.clone();
+    first.save().unwrap();
+
+    let rc = box HeadlessRenderingComponent::with_script(SCREEN_BOUNDS, vec![]);
+    let game = Game::new(rc as Box<RenderingComponent>, Some(7), store);
+    assert_eq!(game.game_state.name(), "resume");
+}